@@ -0,0 +1,280 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::fold;
+
+/*
+ * Aho-Corasick finds all the occurrences of many patterns while reading the haystack only once.
+ *
+ * The patterns are stored in a trie (one node per prefix). Every node also has a "fail" link
+ * pointing to the node of the longest proper suffix of its prefix that is also in the trie:
+ * when the next byte has no edge in the current node, we follow the fail links until we find one
+ * (or we are back at the root), without ever moving backwards in the haystack.
+ *
+ * Searching is O(haystack + matches), no matter how many patterns there are, while calling
+ * "contains" once per pattern is O(haystack * patterns).
+ */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Match {
+    // index of the pattern in the slice given to AhoCorasick::new
+    pub pattern: usize,
+    // byte offsets into the haystack, end excluded
+    pub start: usize,
+    pub end: usize,
+    // edit distance between the pattern and the text matched: always 0 here, see fuzzy.rs
+    pub distance: usize,
+}
+
+struct Node {
+    next: HashMap<u8, usize>,
+    fail: usize,
+    // patterns ending at this node, including the ones reachable through the fail links
+    outputs: Vec<usize>,
+}
+
+pub struct AhoCorasick {
+    nodes: Vec<Node>,
+    // lengths in bytes of the (folded) patterns, used to find where a match starts
+    lengths: Vec<usize>,
+    case_insensitive: bool,
+}
+
+const ROOT: usize = 0;
+
+impl AhoCorasick {
+    pub fn new<S: AsRef<str>>(patterns: &[S], case_insensitive: bool) -> AhoCorasick {
+        let mut ac = AhoCorasick {
+            nodes: vec![Node {
+                next: HashMap::new(),
+                fail: ROOT,
+                outputs: vec![],
+            }],
+            lengths: vec![],
+            case_insensitive,
+        };
+
+        for (id, p) in patterns.iter().enumerate() {
+            // folded once here, so that the haystack is the only thing to fold while searching
+            let p = if case_insensitive {
+                fold::fold_str(p.as_ref())
+            } else {
+                p.as_ref().to_string()
+            };
+            let mut cur = ROOT;
+            for &b in p.as_bytes() {
+                cur = match ac.nodes[cur].next.get(&b) {
+                    Some(&n) => n,
+                    None => {
+                        ac.nodes.push(Node {
+                            next: HashMap::new(),
+                            fail: ROOT,
+                            outputs: vec![],
+                        });
+                        let n = ac.nodes.len() - 1;
+                        ac.nodes[cur].next.insert(b, n);
+                        n
+                    }
+                };
+            }
+            ac.nodes[cur].outputs.push(id);
+            ac.lengths.push(p.len());
+        }
+
+        ac.build_fail_links();
+        ac
+    }
+
+    // breadth-first, so the fail target of a node (always shallower) is complete before the node itself
+    fn build_fail_links(&mut self) {
+        let mut queue: VecDeque<usize> = self.nodes[ROOT].next.values().copied().collect();
+        while let Some(cur) = queue.pop_front() {
+            let edges: Vec<(u8, usize)> =
+                self.nodes[cur].next.iter().map(|(&b, &n)| (b, n)).collect();
+            for (b, child) in edges {
+                let mut f = self.nodes[cur].fail;
+                let fail = loop {
+                    if let Some(&n) = self.nodes[f].next.get(&b) {
+                        break n;
+                    }
+                    if f == ROOT {
+                        break ROOT;
+                    }
+                    f = self.nodes[f].fail;
+                };
+                self.nodes[child].fail = fail;
+                let inherited = self.nodes[fail].outputs.clone();
+                self.nodes[child].outputs.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+    }
+
+    fn step(&self, mut cur: usize, b: u8) -> usize {
+        loop {
+            if let Some(&n) = self.nodes[cur].next.get(&b) {
+                return n;
+            }
+            if cur == ROOT {
+                return ROOT;
+            }
+            cur = self.nodes[cur].fail;
+        }
+    }
+
+    // every occurrence of every pattern, overlapping ones included, ordered by where they end
+    pub fn find_iter(&self, haystack: &str) -> Vec<Match> {
+        let mut res = vec![];
+        // empty patterns are stored in the root and match at the start of every haystack
+        for &pattern in &self.nodes[ROOT].outputs {
+            res.push(Match {
+                pattern,
+                start: 0,
+                end: 0,
+                distance: 0,
+            });
+        }
+
+        let mut cur = ROOT;
+        if !self.case_insensitive {
+            for (i, &b) in haystack.as_bytes().iter().enumerate() {
+                cur = self.step(cur, b);
+                for &pattern in &self.nodes[cur].outputs {
+                    let len = self.lengths[pattern];
+                    if len > 0 {
+                        res.push(Match {
+                            pattern,
+                            start: i + 1 - len,
+                            end: i + 1,
+                            distance: 0,
+                        });
+                    }
+                }
+            }
+            return res;
+        }
+
+        /*
+         * Case insensitive: the automaton reads the folded version of every char. Folding can change
+         * the number of bytes (e.g. 'ß' becomes "ss"), so "starts" remembers, for every folded byte
+         * read so far, where its char starts in the original haystack if it is the first byte of the
+         * char. A match must start and end where the folded text of a char does: a part of "ss" isn't
+         * in the haystack, so "s" doesn't match 'ß'.
+         */
+        let mut starts: Vec<Option<usize>> = vec![];
+        let mut folded = [0; 16];
+        for (offset, c) in haystack.char_indices() {
+            let len = fold_char(c, &mut folded);
+            for (k, &b) in folded[..len].iter().enumerate() {
+                starts.push((k == 0).then_some(offset));
+                cur = self.step(cur, b);
+                if k + 1 < len {
+                    continue;
+                }
+                for &pattern in &self.nodes[cur].outputs {
+                    let len = self.lengths[pattern];
+                    if let (true, Some(start)) = (len > 0, starts[starts.len() - len]) {
+                        res.push(Match {
+                            pattern,
+                            start,
+                            end: offset + c.len_utf8(),
+                            distance: 0,
+                        });
+                    }
+                }
+            }
+        }
+        res
+    }
+
+    // like find_iter, but it stops at the first match and it doesn't allocate anything
+    pub fn is_match(&self, haystack: &str) -> bool {
+        if !self.nodes[ROOT].outputs.is_empty() {
+            return true;
+        }
+        let mut cur = ROOT;
+        if !self.case_insensitive {
+            for &b in haystack.as_bytes() {
+                cur = self.step(cur, b);
+                if !self.nodes[cur].outputs.is_empty() {
+                    return true;
+                }
+            }
+            return false;
+        }
+        let mut folded = [0; 16];
+        for c in haystack.chars() {
+            let len = fold_char(c, &mut folded);
+            for &b in &folded[..len] {
+                cur = self.step(cur, b);
+            }
+            // it may start inside the folded text of a char: find_iter knows, and this is rare
+            if !self.nodes[cur].outputs.is_empty() {
+                return !self.find_iter(haystack).is_empty();
+            }
+        }
+        false
+    }
+}
+
+// the folded bytes of "c", at most 3 chars (see fold.rs): returns how many there are
+fn fold_char(c: char, out: &mut [u8; 16]) -> usize {
+    let mut len = 0;
+    for lc in fold::full(c) {
+        len += lc.encode_utf8(&mut out[len..]).len();
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn found(ac: &AhoCorasick, haystack: &str) -> Vec<(usize, String)> {
+        ac.find_iter(haystack)
+            .iter()
+            .map(|m| (m.pattern, haystack[m.start..m.end].to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_patterns() {
+        let ac = AhoCorasick::new(&["he", "she", "his", "hers"], false);
+        assert_eq!(
+            vec![
+                (1, String::from("she")),
+                (0, String::from("he")),
+                (3, String::from("hers"))
+            ],
+            found(&ac, "ushers")
+        );
+        assert!(!ac.is_match("HERS"));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let ac = AhoCorasick::new(&["N123AB", "bryto"], true);
+        assert_eq!(
+            vec![(0, String::from("n123ab")), (1, String::from("BRYTO"))],
+            found(&ac, "tail n123ab over BRYTO")
+        );
+        // folding 'Ä' to 'ä' keeps the original offsets
+        let ac = AhoCorasick::new(&["ä"], true);
+        assert_eq!(vec![(0, String::from("Ä"))], found(&ac, "xÄx"));
+        // "ß" folds to "ss": the match covers the whole char
+        let ac = AhoCorasick::new(&["STRASSE"], true);
+        assert_eq!(vec![(0, String::from("Straße"))], found(&ac, "Straße 1"));
+        assert!(ac.is_match("an der strasse"));
+        // but half of "ss" isn't in the haystack
+        let ac = AhoCorasick::new(&["s"], true);
+        assert_eq!(vec![(0, String::from("S"))], found(&ac, "Straße"));
+        assert!(!ac.is_match("ß"));
+        assert!(AhoCorasick::new(&["ss"], true).is_match("ß"));
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        let ac = AhoCorasick::new(&[""], false);
+        assert!(ac.is_match(""));
+        assert!(ac.is_match("anything"));
+    }
+}
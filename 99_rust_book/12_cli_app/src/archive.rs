@@ -0,0 +1,290 @@
+use crate::inflate::inflate;
+
+/*
+ * "--search-zip" looks inside compressed files and archives, without spawning gzip or tar:
+ * - "x.gz" is decompressed and searched as a single file
+ * - "x.tar" is split into its files, every one searched on its own as "x.tar:inner/path"
+ * - "x.tar.gz" and "x.tgz" are both things, one after the other
+ *
+ * Any other file is searched as it is.
+ */
+
+// the name to show in the results and the bytes to search
+pub type Entry = (String, Vec<u8>);
+
+pub fn is_archive(path: &str) -> bool {
+    path.ends_with(".gz") || path.ends_with(".tgz") || path.ends_with(".tar")
+}
+
+pub fn entries(path: &str, bytes: Vec<u8>) -> Result<Vec<Entry>, String> {
+    let (bytes, inner) = if let Some(stem) = path.strip_suffix(".gz") {
+        (gunzip(&bytes)?, stem.to_string())
+    } else if let Some(stem) = path.strip_suffix(".tgz") {
+        (gunzip(&bytes)?, format!("{}.tar", stem))
+    } else {
+        (bytes, path.to_string())
+    };
+
+    if inner.ends_with(".tar") {
+        let files = untar(&bytes)?;
+        return Ok(files
+            .into_iter()
+            .map(|(name, data)| (format!("{}:{}", path, name), data))
+            .collect());
+    }
+    Ok(vec![(path.to_string(), bytes)])
+}
+
+/*
+ * gzip (RFC 1952) is a header, DEFLATE data and a trailer with the CRC-32 and the size of the
+ * uncompressed data. A file can have many of these "members" one after the other (e.g. "cat a.gz b.gz"),
+ * their content is concatenated. Zeros after the last member are padding (from dd or a tape, which
+ * write whole blocks), and are ignored like gzip does.
+ */
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+
+    let mut out = vec![];
+    let mut p = 0;
+    while p < data.len() {
+        if p > 0 && data[p..].iter().all(|&b| b == 0) {
+            break;
+        }
+        let header = data.get(p..p + 10).ok_or("truncated gzip header")?;
+        if header[0..2] != [0x1f, 0x8b] {
+            return Err(String::from("not a gzip file"));
+        }
+        if header[2] != 8 {
+            return Err(String::from("unknown gzip compression method"));
+        }
+        let flags = header[3];
+        p += 10;
+        if flags & FEXTRA != 0 {
+            let len = data.get(p..p + 2).ok_or("truncated gzip header")?;
+            p += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+        }
+        // the original file name and a comment, both NUL terminated
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let nul = data[p.min(data.len())..]
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or("truncated gzip header")?;
+                p += nul + 1;
+            }
+        }
+        if flags & FHCRC != 0 {
+            p += 2;
+        }
+
+        let (member, used) = inflate(data.get(p..).ok_or("truncated gzip header")?)?;
+        p += used;
+        let trailer = data.get(p..p + 8).ok_or("truncated gzip trailer")?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != crc32(&member) || size != member.len() as u32 {
+            return Err(String::from("gzip data is corrupted (checksum mismatch)"));
+        }
+        p += 8;
+        out.extend(member);
+    }
+    Ok(out)
+}
+
+// CRC-32 as used by gzip (polynomial 0xEDB88320), one bit at a time
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/*
+ * tar is a sequence of 512 bytes blocks: a header block for every file (name, size, type, ...)
+ * followed by the content of the file, padded to a multiple of 512. Two blocks of zeros end the archive.
+ *
+ * Only regular files are returned. Names longer than 100 bytes are found in the "prefix" field (ustar),
+ * in a previous "L" entry (GNU) or in the "path" record of a previous "x" entry (pax).
+ */
+pub fn untar(data: &[u8]) -> Result<Vec<Entry>, String> {
+    const BLOCK: usize = 512;
+
+    let mut files = vec![];
+    let mut long_name: Option<String> = None;
+    let mut p = 0;
+    while p + BLOCK <= data.len() {
+        let h = &data[p..p + BLOCK];
+        if h.iter().all(|&b| b == 0) {
+            break;
+        }
+        if checksum(h) != octal(&h[148..156])? {
+            return Err(format!("corrupted tar header at offset {}", p));
+        }
+
+        let truncated = || format!("truncated tar entry at offset {}", p);
+        // a size past the end of the data (even past what a usize holds) can only be a truncated tar
+        let start = p + BLOCK;
+        let end = usize::try_from(octal(&h[124..136])?)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(truncated)?;
+        let size = end - start;
+        let content = data.get(start..end).ok_or_else(truncated)?;
+        p = start + size.div_ceil(BLOCK) * BLOCK;
+
+        match h[156] {
+            b'L' => long_name = Some(field(content)),
+            b'x' => {
+                if let Some(path) = pax_path(content) {
+                    long_name = Some(path);
+                }
+            }
+            b'0' | 0 => {
+                let name = match long_name.take() {
+                    Some(n) => n,
+                    None if &h[257..262] == b"ustar" && h[345] != 0 => {
+                        format!("{}/{}", field(&h[345..500]), field(&h[0..100]))
+                    }
+                    None => field(&h[0..100]),
+                };
+                files.push((name, content.to_vec()));
+            }
+            // directories, links, devices...
+            _ => long_name = None,
+        }
+    }
+    Ok(files)
+}
+
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+// numbers are stored as octal text, or in binary (big endian) when the first bit is set (GNU)
+fn octal(bytes: &[u8]) -> Result<u64, String> {
+    if bytes[0] & 0x80 != 0 {
+        let mut n = (bytes[0] & 0x7f) as u64;
+        for &b in &bytes[1..] {
+            n = (n << 8) | b as u64;
+        }
+        return Ok(n);
+    }
+    let text = field(bytes);
+    let text = text.trim_matches(' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).map_err(|_| format!("invalid number in tar header: {:?}", text))
+}
+
+// the sum of the bytes of the header, with the checksum field itself counted as spaces
+fn checksum(h: &[u8]) -> u64 {
+    h.iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+        .sum()
+}
+
+// pax records look like "30 path=some/very/long/name\n", the number being the length of the record
+fn pax_path(content: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(content);
+    text.lines()
+        .filter_map(|l| l.split_once(' ').map(|(_, kv)| kv))
+        .find_map(|kv| kv.strip_prefix("path=").map(String::from))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(name: &str, kind: u8, size: usize) -> Vec<u8> {
+        let mut h = vec![0u8; 512];
+        h[..name.len()].copy_from_slice(name.as_bytes());
+        h[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        h[156] = kind;
+        h[257..263].copy_from_slice(b"ustar\0");
+        let sum = checksum(&h);
+        h[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        h
+    }
+
+    fn tar(entries: &[(&str, u8, &[u8])]) -> Vec<u8> {
+        let mut t = vec![];
+        for (name, kind, content) in entries {
+            t.extend(header(name, *kind, content.len()));
+            t.extend_from_slice(content);
+            t.resize(t.len().div_ceil(512) * 512, 0);
+        }
+        t.extend(vec![0; 1024]);
+        t
+    }
+
+    #[test]
+    fn tar_entries() {
+        let long = "logs/".repeat(30) + "flight.log";
+        let t = tar(&[
+            ("logs/", b'5', b""),
+            ("logs/a.log", b'0', b"ALT 350\n"),
+            ("././@LongLink", b'L', long.as_bytes()),
+            ("ignored", b'0', b"ENGINE FIRE\n"),
+        ]);
+
+        let files = untar(&t).unwrap();
+        assert_eq!(
+            vec![
+                (String::from("logs/a.log"), b"ALT 350\n".to_vec()),
+                (long, b"ENGINE FIRE\n".to_vec())
+            ],
+            files
+        );
+
+        // a base-256 size that doesn't fit anywhere
+        let mut h = header("huge", b'0', 0);
+        h[124..136].copy_from_slice(&[0xff; 12]);
+        let sum = checksum(&h);
+        h[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        assert_eq!(
+            Err(String::from("truncated tar entry at offset 0")),
+            untar(&h)
+        );
+    }
+
+    // produced with Python's gzip.compress(b"ALT 350\n", mtime=0)
+    const GZIP: [u8; 28] = [
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 115, 244, 9, 81, 48, 54, 53, 224, 2, 0, 43, 200, 205, 170,
+        8, 0, 0, 0,
+    ];
+
+    #[test]
+    fn gzip_members() {
+        assert_eq!(b"ALT 350\n".to_vec(), gunzip(&GZIP).unwrap());
+        let twice = [GZIP, GZIP].concat();
+        assert_eq!(b"ALT 350\nALT 350\n".to_vec(), gunzip(&twice).unwrap());
+        let padded = [&GZIP[..], &[0; 512]].concat();
+        assert_eq!(b"ALT 350\n".to_vec(), gunzip(&padded).unwrap());
+        // but a file of zeros isn't a gzip file
+        assert!(gunzip(&[0; 512]).is_err());
+
+        let mut corrupted = GZIP;
+        corrupted[20] ^= 1;
+        assert!(gunzip(&corrupted).is_err());
+    }
+
+    #[test]
+    fn archive_names() {
+        let t = tar(&[("inner/path.log", b'0', b"x\n")]);
+        let e = entries("day.tar", t).unwrap();
+        assert_eq!("day.tar:inner/path.log", e[0].0);
+        let e = entries("a.log.gz", GZIP.to_vec()).unwrap();
+        assert_eq!((String::from("a.log.gz"), b"ALT 350\n".to_vec()), e[0]);
+    }
+}
@@ -0,0 +1,1494 @@
+use std::{error::Error, fmt};
+
+use crate::{
+    expr::{self, ExprError, Query},
+    searcher::Strategy,
+};
+
+/*
+ * A small hand-written argument parser. It supports:
+ * - short flags, also combined: "-in" is the same as "-i -n"
+ * - short flags with a value, attached or detached: "-m3" or "-m 3"
+ * - long flags, with the value after "=" or in the next argument: "--max-count=3" or "--max-count 3"
+ * - "--" to stop parsing flags: everything after it is a positional argument (useful to search for "-x")
+ * - repeatable flags: "-e N123AB -e N456CD" searches for both patterns
+ *
+ * Every flag is described once in FLAGS, so "--help" is generated from the same table the parser uses.
+ */
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CaseMode {
+    Sensitive,
+    Insensitive,
+    // insensitive, unless the query contains an uppercase character
+    Smart,
+}
+
+pub struct Config {
+    // the QUERY positional, or every "-e" pattern
+    pub patterns: Vec<String>,
+    // files given with "-f", one pattern per line: they are read by "run"
+    pub pattern_files: Vec<String>,
+    // one or more files, searched in parallel
+    pub files: Vec<String>,
+    pub case: CaseMode,
+    pub line_number: bool,
+    // per file, like grep
+    pub max_count: Option<usize>,
+    pub threads: usize,
+    pub sort: SortBy,
+    // lines to print before and after every match
+    pub context: usize,
+    pub json: bool,
+    // the text substituted to every match
+    pub replace: Option<String>,
+    // rewrite the files with the replacements instead of printing them
+    pub in_place: bool,
+    // keep a copy of every rewritten file in "FILE.bak"
+    pub backup: bool,
+    pub normalize: Option<Normalization>,
+    // print the column of the first match, counted in graphemes
+    pub column: bool,
+    // search the binary files as if they were text
+    pub text: bool,
+    pub encoding: Encoding,
+    // look inside .gz, .tar, .tar.gz and .tgz files
+    pub search_zip: bool,
+    // the positionals after the patterns are indexed directories: only the candidate files are searched
+    pub index: bool,
+    // QUERY parsed as a boolean expression, its terms are then the patterns
+    pub expr: Option<Query>,
+    // evaluate the expression on the paragraphs (separated by blank lines) instead of single lines
+    pub paragraph: bool,
+    // the maximum edit distance of an approximate match
+    pub fuzzy: Option<usize>,
+    // search the whole content at once, so that a match can span many lines
+    pub multiline: bool,
+    // keep reading the lines added to the files, like "tail -F"
+    pub follow: bool,
+    // search for the single pattern with this algorithm instead of Aho-Corasick
+    pub searcher: Option<Strategy>,
+    // read the files as CSV or TSV: the records are matched instead of the lines
+    pub table: Option<Table>,
+    // type the query in a full screen UI: there is no QUERY positional
+    pub interactive: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    // UTF-8, unless a BOM says the file is UTF-16
+    Auto,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+// Unicode normalization forms: "é" can be one char (NFC) or 'e' followed by a combining accent (NFD)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Normalization {
+    Nfc,
+    Nfd,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Table {
+    // ',' or a tab
+    pub delimiter: char,
+    // the only column searched, all of them by default
+    pub field: Option<Column>,
+    // the columns printed, in this order: all of them when empty
+    pub select: Vec<Column>,
+}
+
+// a column of a table, as given by the user: its name in the header, or its number starting from 1
+#[derive(Debug, PartialEq, Clone)]
+pub enum Column {
+    Name(String),
+    Number(usize),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortBy {
+    // print every file as soon as it has been searched
+    None,
+    Path,
+}
+
+impl CaseMode {
+    pub fn is_sensitive<S: AsRef<str>>(self, patterns: &[S]) -> bool {
+        match self {
+            CaseMode::Sensitive => true,
+            CaseMode::Insensitive => false,
+            CaseMode::Smart => patterns
+                .iter()
+                .any(|p| p.as_ref().chars().any(char::is_uppercase)),
+        }
+    }
+}
+
+// what the user asked the program to do
+pub enum Command {
+    // boxed, as it is much bigger than the other variants
+    Search(Box<Config>),
+    // "minigrep index DIR...": builds or refreshes the trigram index of every DIR
+    Index(Vec<String>),
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArgError {
+    UnknownFlag {
+        position: usize,
+        flag: String,
+    },
+    MissingValue {
+        position: usize,
+        flag: String,
+    },
+    InvalidValue {
+        position: usize,
+        flag: String,
+        value: String,
+    },
+    UnexpectedValue {
+        position: usize,
+        flag: String,
+    },
+    UnexpectedArgument {
+        position: usize,
+        arg: String,
+    },
+    Requires {
+        position: usize,
+        flag: String,
+        requires: String,
+    },
+    Conflicts {
+        position: usize,
+        flag: String,
+        with: String,
+    },
+    InvalidExpression {
+        position: usize,
+        query: String,
+        error: ExprError,
+    },
+    MissingQuery,
+    MissingFilename,
+    MissingDirectory,
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::UnknownFlag { position, flag } => {
+                write!(f, "unknown flag '{}' (argument {})", flag, position)
+            }
+            ArgError::MissingValue { position, flag } => {
+                write!(f, "flag '{}' needs a value (argument {})", flag, position)
+            }
+            ArgError::InvalidValue {
+                position,
+                flag,
+                value,
+            } => write!(
+                f,
+                "invalid value '{}' for flag '{}' (argument {})",
+                value, flag, position
+            ),
+            ArgError::UnexpectedValue { position, flag } => {
+                write!(
+                    f,
+                    "flag '{}' doesn't take a value (argument {})",
+                    flag, position
+                )
+            }
+            ArgError::UnexpectedArgument { position, arg } => {
+                write!(f, "unexpected argument '{}' (argument {})", arg, position)
+            }
+            ArgError::Requires {
+                position,
+                flag,
+                requires,
+            } => write!(
+                f,
+                "flag '{}' can only be used with '{}' (argument {})",
+                flag, requires, position
+            ),
+            ArgError::Conflicts {
+                position,
+                flag,
+                with,
+            } => write!(
+                f,
+                "flag '{}' can't be used with '{}' (argument {})",
+                flag, with, position
+            ),
+            ArgError::InvalidExpression {
+                position,
+                query,
+                error,
+            } => write!(
+                f,
+                "invalid expression (argument {}): {}",
+                position,
+                error.show(query)
+            ),
+            ArgError::MissingQuery => write!(f, "query string missing"),
+            ArgError::MissingFilename => write!(f, "filename missing"),
+            ArgError::MissingDirectory => write!(f, "directory missing"),
+        }
+    }
+}
+
+// Display + Debug are all that Error needs, so "?" can turn an ArgError into a Box<dyn Error>
+impl Error for ArgError {}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Opt {
+    IgnoreCase,
+    CaseSensitive,
+    SmartCase,
+    LineNumber,
+    Column,
+    Normalize,
+    Text,
+    Encoding,
+    SearchZip,
+    Index,
+    Expr,
+    Paragraph,
+    Fuzzy,
+    Multiline,
+    Follow,
+    Searcher,
+    Csv,
+    Tsv,
+    Field,
+    Select,
+    Interactive,
+    MaxCount,
+    Context,
+    Json,
+    Replace,
+    InPlace,
+    Backup,
+    Threads,
+    Sort,
+    Regexp,
+    File,
+    Help,
+    Version,
+}
+
+struct Flag {
+    opt: Opt,
+    short: Option<char>,
+    long: &'static str,
+    // name of the value shown in the help, None if the flag doesn't take a value
+    value: Option<&'static str>,
+    help: &'static str,
+}
+
+const FLAGS: &[Flag] = &[
+    Flag {
+        opt: Opt::IgnoreCase,
+        short: Some('i'),
+        long: "ignore-case",
+        value: None,
+        help: "search case insensitively",
+    },
+    Flag {
+        opt: Opt::CaseSensitive,
+        short: Some('s'),
+        long: "case-sensitive",
+        value: None,
+        help: "search case sensitively",
+    },
+    Flag {
+        opt: Opt::SmartCase,
+        short: Some('S'),
+        long: "smart-case",
+        value: None,
+        help: "search case insensitively unless QUERY has an uppercase character",
+    },
+    Flag {
+        opt: Opt::LineNumber,
+        short: Some('n'),
+        long: "line-number",
+        value: None,
+        help: "prefix each line with its line number",
+    },
+    Flag {
+        opt: Opt::Column,
+        short: None,
+        long: "column",
+        value: None,
+        help: "prefix each line with the column of its first match, in graphemes",
+    },
+    Flag {
+        opt: Opt::Normalize,
+        short: None,
+        long: "normalize",
+        value: Some("nfc|nfd"),
+        help: "normalize the patterns and the files to the same Unicode form before searching",
+    },
+    Flag {
+        opt: Opt::Text,
+        short: Some('a'),
+        long: "text",
+        value: None,
+        help: "search binary files as if they were text",
+    },
+    Flag {
+        opt: Opt::Encoding,
+        short: None,
+        long: "encoding",
+        value: Some("auto|utf8|utf16le|utf16be|latin1"),
+        help: "read the files with this encoding (default: UTF-8, or what their BOM says)",
+    },
+    Flag {
+        opt: Opt::SearchZip,
+        short: Some('z'),
+        long: "search-zip",
+        value: None,
+        help: "search inside gzip files and tar archives",
+    },
+    Flag {
+        opt: Opt::Index,
+        short: None,
+        long: "index",
+        value: None,
+        help: "FILE... are directories indexed with \"minigrep index\": only search their candidate files",
+    },
+    Flag {
+        opt: Opt::Expr,
+        short: None,
+        long: "expr",
+        value: None,
+        help: "QUERY is a boolean expression of words and \"phrases\" with AND, OR, NOT and parentheses",
+    },
+    Flag {
+        opt: Opt::Paragraph,
+        short: None,
+        long: "paragraph",
+        value: None,
+        help: "with --expr, match whole paragraphs (separated by blank lines) instead of lines",
+    },
+    Flag {
+        opt: Opt::Fuzzy,
+        short: None,
+        long: "fuzzy",
+        value: Some("K"),
+        help: "also match the text within K edits (chars inserted, deleted or changed) of a pattern",
+    },
+    Flag {
+        opt: Opt::Multiline,
+        short: Some('U'),
+        long: "multiline",
+        value: None,
+        help: "let the matches span many lines (\\n in a pattern is a newline) and print the lines they cover",
+    },
+    Flag {
+        opt: Opt::Follow,
+        short: None,
+        long: "follow",
+        value: None,
+        help: "print the matching lines appended to FILE... as they arrive, following truncations and rotations",
+    },
+    Flag {
+        opt: Opt::Searcher,
+        short: None,
+        long: "searcher",
+        value: Some("naive|iter|two-way|simd"),
+        help: "find the only pattern, case sensitively, with this algorithm (see the bench binary)",
+    },
+    Flag {
+        opt: Opt::Csv,
+        short: None,
+        long: "csv",
+        value: None,
+        help: "read FILE... as CSV, with a header: print the header and the matching records",
+    },
+    Flag {
+        opt: Opt::Tsv,
+        short: None,
+        long: "tsv",
+        value: None,
+        help: "the same as --csv, with the fields separated by tabs",
+    },
+    Flag {
+        opt: Opt::Field,
+        short: None,
+        long: "field",
+        value: Some("NAME|N"),
+        help: "with --csv or --tsv, only search the column named NAME (or the Nth one, from 1)",
+    },
+    Flag {
+        opt: Opt::Select,
+        short: None,
+        long: "select",
+        value: Some("COLUMNS"),
+        help: "with --csv or --tsv, only print these comma-separated columns (names or numbers)",
+    },
+    Flag {
+        opt: Opt::Interactive,
+        short: None,
+        long: "interactive",
+        value: None,
+        help: "type the query and browse the matches of FILE... as you type (Enter prints the selected line)",
+    },
+    Flag {
+        opt: Opt::MaxCount,
+        short: Some('m'),
+        long: "max-count",
+        value: Some("NUM"),
+        help: "stop after NUM matching lines in each file",
+    },
+    Flag {
+        opt: Opt::Context,
+        short: Some('C'),
+        long: "context",
+        value: Some("NUM"),
+        help: "print NUM lines before and after every match",
+    },
+    Flag {
+        opt: Opt::Json,
+        short: None,
+        long: "json",
+        value: None,
+        help: "print the results as JSON Lines, in the same format as ripgrep",
+    },
+    Flag {
+        opt: Opt::Replace,
+        short: Some('r'),
+        long: "replace",
+        value: Some("TEXT"),
+        help: "print the matching lines with every match replaced by TEXT (taken literally)",
+    },
+    Flag {
+        opt: Opt::InPlace,
+        short: None,
+        long: "in-place",
+        value: None,
+        help: "write the replacements back to the files instead of printing them",
+    },
+    Flag {
+        opt: Opt::Backup,
+        short: None,
+        long: "backup",
+        value: None,
+        help: "with --in-place, save the original of every changed FILE in FILE.bak",
+    },
+    Flag {
+        opt: Opt::Threads,
+        short: Some('j'),
+        long: "threads",
+        value: Some("NUM"),
+        help: "search NUM files at a time (default: the number of CPUs)",
+    },
+    Flag {
+        opt: Opt::Sort,
+        short: None,
+        long: "sort",
+        value: Some("path|none"),
+        help: "print the files sorted by path instead of as soon as they are searched",
+    },
+    Flag {
+        opt: Opt::Regexp,
+        short: Some('e'),
+        long: "regexp",
+        value: Some("PATTERN"),
+        help: "search for PATTERN, can be given many times (QUERY is then omitted)",
+    },
+    Flag {
+        opt: Opt::File,
+        short: Some('f'),
+        long: "file",
+        value: Some("FILE"),
+        help: "search for the patterns in FILE, one per line (QUERY is then omitted)",
+    },
+    Flag {
+        opt: Opt::Help,
+        short: Some('h'),
+        long: "help",
+        value: None,
+        help: "print this help and exit",
+    },
+    Flag {
+        opt: Opt::Version,
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "print the version and exit",
+    },
+];
+
+pub fn help() -> String {
+    let mut out = format!(
+        "{} {}\n\nUSAGE:\n    minigrep [FLAGS] QUERY FILE...\n    minigrep [FLAGS] -e PATTERN... FILE...\n    minigrep index DIR...\n\nFLAGS:\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let usages: Vec<String> = FLAGS.iter().map(usage).collect();
+    let width = usages.iter().map(|u| u.len()).max().unwrap_or(0);
+    for (u, f) in usages.iter().zip(FLAGS) {
+        out.push_str(&format!("    {:width$}    {}\n", u, f.help, width = width));
+    }
+    out.push_str(
+        "\nWithout a case flag, the search is case sensitive only if CASE_SENSITIVE is set.\n",
+    );
+    out.push_str(
+        "NOT binds tighter than AND, which binds tighter than OR, and terms next to each other are ANDed:\n    minigrep --expr '(fuel AND low) OR \"engine fire\" NOT test' FILE\n",
+    );
+    out
+}
+
+pub fn version() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+fn usage(f: &Flag) -> String {
+    let short = match f.short {
+        Some(c) => format!("-{}, ", c),
+        None => String::from("    "),
+    };
+    match f.value {
+        Some(v) => format!("{}--{} <{}>", short, f.long, v),
+        None => format!("{}--{}", short, f.long),
+    }
+}
+
+// partially parsed config, turned into a Config once all the arguments have been read
+struct Builder {
+    case: CaseMode,
+    line_number: bool,
+    max_count: Option<usize>,
+    threads: usize,
+    sort: SortBy,
+    context: usize,
+    json: bool,
+    replace: Option<String>,
+    // positions of the flags, for the errors about missing companions
+    in_place: Option<usize>,
+    backup: Option<usize>,
+    normalize: Option<(usize, Normalization)>,
+    column: bool,
+    text: bool,
+    encoding: Encoding,
+    search_zip: bool,
+    index: Option<usize>,
+    expr: Option<usize>,
+    paragraph: Option<usize>,
+    fuzzy: Option<(usize, usize)>,
+    multiline: Option<usize>,
+    follow: Option<usize>,
+    searcher: Option<(usize, Strategy)>,
+    csv: Option<usize>,
+    tsv: Option<usize>,
+    field: Option<(usize, Column)>,
+    select: Option<(usize, Vec<Column>)>,
+    interactive: Option<usize>,
+    patterns: Vec<String>,
+    pattern_files: Vec<String>,
+    positionals: Vec<(usize, String)>,
+}
+
+impl Builder {
+    fn apply(
+        &mut self,
+        opt: Opt,
+        position: usize,
+        value: Option<(usize, String)>,
+        name: &str,
+    ) -> Result<(), ArgError> {
+        match opt {
+            Opt::IgnoreCase => self.case = CaseMode::Insensitive,
+            Opt::CaseSensitive => self.case = CaseMode::Sensitive,
+            Opt::SmartCase => self.case = CaseMode::Smart,
+            Opt::LineNumber => self.line_number = true,
+            Opt::Column => self.column = true,
+            Opt::Text => self.text = true,
+            Opt::SearchZip => self.search_zip = true,
+            Opt::Index => self.index = Some(position),
+            Opt::Expr => self.expr = Some(position),
+            Opt::Paragraph => self.paragraph = Some(position),
+            Opt::Multiline => self.multiline = Some(position),
+            Opt::Follow => self.follow = Some(position),
+            Opt::Searcher => {
+                let (value_position, value) = value.unwrap();
+                let strategy =
+                    Strategy::from_name(&value).ok_or_else(|| ArgError::InvalidValue {
+                        position: value_position,
+                        flag: name.to_string(),
+                        value,
+                    })?;
+                self.searcher = Some((position, strategy));
+            }
+            Opt::Interactive => self.interactive = Some(position),
+            Opt::Csv => self.csv = Some(position),
+            Opt::Tsv => self.tsv = Some(position),
+            Opt::Field => {
+                let (value_position, value) = value.unwrap();
+                self.field = Some((position, column((value_position, &value), name)?));
+            }
+            Opt::Select => {
+                let (value_position, value) = value.unwrap();
+                let columns = value
+                    .split(',')
+                    .map(|c| column((value_position, c), name))
+                    .collect::<Result<_, _>>()?;
+                self.select = Some((position, columns));
+            }
+            Opt::Fuzzy => self.fuzzy = Some((position, number(value.unwrap(), name)?)),
+            Opt::Encoding => {
+                let (position, value) = value.unwrap();
+                self.encoding = match value.as_str() {
+                    "auto" => Encoding::Auto,
+                    "utf8" => Encoding::Utf8,
+                    "utf16le" => Encoding::Utf16Le,
+                    "utf16be" => Encoding::Utf16Be,
+                    "latin1" => Encoding::Latin1,
+                    _ => {
+                        return Err(ArgError::InvalidValue {
+                            position,
+                            flag: name.to_string(),
+                            value,
+                        })
+                    }
+                };
+            }
+            Opt::Normalize => {
+                let (position, value) = value.unwrap();
+                let form = match value.as_str() {
+                    "nfc" => Normalization::Nfc,
+                    "nfd" => Normalization::Nfd,
+                    _ => {
+                        return Err(ArgError::InvalidValue {
+                            position,
+                            flag: name.to_string(),
+                            value,
+                        })
+                    }
+                };
+                self.normalize = Some((position, form));
+            }
+            // flags taking a value are always called with Some
+            Opt::MaxCount => self.max_count = Some(number(value.unwrap(), name)?),
+            Opt::Context => self.context = number(value.unwrap(), name)?,
+            Opt::Json => self.json = true,
+            Opt::Replace => self.replace = Some(value.unwrap().1),
+            Opt::InPlace => self.in_place = Some(position),
+            Opt::Backup => self.backup = Some(position),
+            Opt::Threads => self.threads = number(value.unwrap(), name)?,
+            Opt::Sort => {
+                let (position, value) = value.unwrap();
+                self.sort = match value.as_str() {
+                    "path" => SortBy::Path,
+                    "none" => SortBy::None,
+                    _ => {
+                        return Err(ArgError::InvalidValue {
+                            position,
+                            flag: name.to_string(),
+                            value,
+                        })
+                    }
+                };
+            }
+            Opt::Regexp => self.patterns.push(value.unwrap().1),
+            Opt::File => self.pattern_files.push(value.unwrap().1),
+            // handled by parse before reaching here
+            Opt::Help | Opt::Version => {}
+        }
+        Ok(())
+    }
+}
+
+fn number((position, value): (usize, String), name: &str) -> Result<usize, ArgError> {
+    value.parse().map_err(|_| ArgError::InvalidValue {
+        position,
+        flag: name.to_string(),
+        value,
+    })
+}
+
+// a number is a column number, anything else the name of a column
+fn column((position, value): (usize, &str), name: &str) -> Result<Column, ArgError> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(Column::Number(n)),
+        Err(_) if !value.is_empty() => Ok(Column::Name(value.to_string())),
+        _ => Err(ArgError::InvalidValue {
+            position,
+            flag: name.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/*
+ * "args" is the whole command line, program name included (what env::args() returns).
+ * Positions in the errors are indices into "args", so "argument 1" is the first one after the program name.
+ */
+pub fn parse(args: &[String]) -> Result<Command, ArgError> {
+    // to search for the word "index", use "minigrep -- index FILE" or "minigrep -e index FILE"
+    if args.get(1).is_some_and(|a| a == "index") {
+        return parse_index(args);
+    }
+
+    let default_case = if std::env::var("CASE_SENSITIVE").is_ok() {
+        CaseMode::Sensitive
+    } else {
+        CaseMode::Insensitive
+    };
+    let mut b = Builder {
+        case: default_case,
+        line_number: false,
+        max_count: None,
+        threads: 0,
+        sort: SortBy::None,
+        context: 0,
+        json: false,
+        replace: None,
+        in_place: None,
+        backup: None,
+        normalize: None,
+        column: false,
+        text: false,
+        encoding: Encoding::Auto,
+        search_zip: false,
+        index: None,
+        expr: None,
+        paragraph: None,
+        fuzzy: None,
+        multiline: None,
+        follow: None,
+        searcher: None,
+        csv: None,
+        tsv: None,
+        field: None,
+        select: None,
+        interactive: None,
+        patterns: vec![],
+        pattern_files: vec![],
+        positionals: vec![],
+    };
+
+    let mut only_positionals = false;
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        let position = i;
+        i += 1;
+
+        // "-" alone usually means stdin, so it is treated as a positional
+        if only_positionals || arg == "-" || !arg.starts_with('-') {
+            b.positionals.push((position, arg.clone()));
+            continue;
+        }
+        if arg == "--" {
+            only_positionals = true;
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (long, None),
+            };
+            let name = format!("--{}", name);
+            let flag = FLAGS
+                .iter()
+                .find(|f| format!("--{}", f.long) == name)
+                .ok_or_else(|| ArgError::UnknownFlag {
+                    position,
+                    flag: name.clone(),
+                })?;
+            let value = match (flag.value, inline) {
+                (Some(_), Some(v)) => Some((position, v)),
+                (Some(_), None) => {
+                    let v = args.get(i).ok_or_else(|| ArgError::MissingValue {
+                        position,
+                        flag: name.clone(),
+                    })?;
+                    i += 1;
+                    Some((position + 1, v.clone()))
+                }
+                (None, Some(_)) => {
+                    return Err(ArgError::UnexpectedValue {
+                        position,
+                        flag: name,
+                    })
+                }
+                (None, None) => None,
+            };
+            match flag.opt {
+                Opt::Help => return Ok(Command::Help),
+                Opt::Version => return Ok(Command::Version),
+                opt => b.apply(opt, position, value, &name)?,
+            }
+            continue;
+        }
+
+        // a cluster of short flags: the first one taking a value consumes the rest of the cluster
+        let cluster = &arg[1..];
+        for (idx, c) in cluster.char_indices() {
+            let name = format!("-{}", c);
+            let flag =
+                FLAGS
+                    .iter()
+                    .find(|f| f.short == Some(c))
+                    .ok_or_else(|| ArgError::UnknownFlag {
+                        position,
+                        flag: name.clone(),
+                    })?;
+            let value = if flag.value.is_some() {
+                let rest = &cluster[idx + c.len_utf8()..];
+                if !rest.is_empty() {
+                    Some((position, rest.to_string()))
+                } else {
+                    let v = args.get(i).ok_or_else(|| ArgError::MissingValue {
+                        position,
+                        flag: name.clone(),
+                    })?;
+                    i += 1;
+                    Some((position + 1, v.clone()))
+                }
+            } else {
+                None
+            };
+            let takes_value = value.is_some();
+            match flag.opt {
+                Opt::Help => return Ok(Command::Help),
+                Opt::Version => return Ok(Command::Version),
+                opt => b.apply(opt, position, value, &name)?,
+            }
+            if takes_value {
+                break;
+            }
+        }
+    }
+
+    let mut positionals = b.positionals.into_iter();
+    // with "-e" or "-f" the first positional is already the file to search
+    let mut patterns = b.patterns;
+    let mut query_position = None;
+    // with "--interactive" the query is typed later, every positional is a file
+    if patterns.is_empty() && b.pattern_files.is_empty() && b.interactive.is_none() {
+        let (position, query) = positionals.next().ok_or(ArgError::MissingQuery)?;
+        query_position = Some(position);
+        patterns.push(query);
+    }
+    let files: Vec<String> = positionals.map(|(_, f)| f).collect();
+    if files.is_empty() {
+        return Err(ArgError::MissingFilename);
+    }
+
+    if let (Some(position), None) = (b.in_place, &b.replace) {
+        return Err(ArgError::Requires {
+            position,
+            flag: String::from("--in-place"),
+            requires: String::from("--replace"),
+        });
+    }
+    if let (Some(position), None) = (b.backup, b.in_place) {
+        return Err(ArgError::Requires {
+            position,
+            flag: String::from("--backup"),
+            requires: String::from("--in-place"),
+        });
+    }
+    // the rewritten file would be normalized as a whole, not just where the matches are
+    if let (Some((position, _)), Some(_)) = (b.normalize, b.in_place) {
+        return Err(ArgError::Conflicts {
+            position,
+            flag: String::from("--normalize"),
+            with: String::from("--in-place"),
+        });
+    }
+    // a rewrite only tells how many replacements it made, in plain text: not a JSON Lines stream
+    if let (Some(position), true) = (b.in_place, b.json) {
+        return Err(ArgError::Conflicts {
+            position,
+            flag: String::from("--in-place"),
+            with: String::from("--json"),
+        });
+    }
+
+    if let (Some(position), None) = (b.paragraph, b.expr) {
+        return Err(ArgError::Requires {
+            position,
+            flag: String::from("--paragraph"),
+            requires: String::from("--expr"),
+        });
+    }
+    if let Some((position, _)) = b.fuzzy {
+        // an expression is made of exact terms, and the index only knows about exact trigrams
+        let with = match (b.expr, b.index) {
+            (Some(_), _) => Some("--expr"),
+            (_, Some(_)) => Some("--index"),
+            _ => None,
+        };
+        if let Some(with) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--fuzzy"),
+                with: String::from(with),
+            });
+        }
+    }
+    if let Some(position) = b.index {
+        // the index is built from the files as "--encoding auto" reads them, and not normalized: with
+        // another view of the text, it could rule out a file that matches
+        let with = [
+            (b.normalize.is_some(), "--normalize"),
+            (b.encoding != Encoding::Auto, "--encoding"),
+        ]
+        .into_iter()
+        .find(|(set, _)| *set);
+        if let Some((_, with)) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--index"),
+                with: String::from(with),
+            });
+        }
+    }
+    if let Some(position) = b.multiline {
+        // these ones work line by line
+        let with = match (b.expr, b.fuzzy, b.context) {
+            (Some(_), _, _) => Some("--expr"),
+            (_, Some(_), _) => Some("--fuzzy"),
+            (_, _, c) if c > 0 => Some("--context"),
+            _ => None,
+        };
+        if let Some(with) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--multiline"),
+                with: String::from(with),
+            });
+        }
+    }
+    if let Some(position) = b.follow {
+        // the new lines are matched one at a time, as they arrive
+        let with = [
+            (b.in_place.is_some(), "--in-place"),
+            (b.json, "--json"),
+            (b.multiline.is_some(), "--multiline"),
+            (b.expr.is_some(), "--expr"),
+            (b.fuzzy.is_some(), "--fuzzy"),
+            (b.index.is_some(), "--index"),
+            (b.search_zip, "--search-zip"),
+            (b.context > 0, "--context"),
+        ]
+        .into_iter()
+        .find(|(set, _)| *set);
+        if let Some((_, with)) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--follow"),
+                with: String::from(with),
+            });
+        }
+    }
+    if let Some((position, _)) = b.searcher {
+        // the searchers only know how to find one exact pattern, line by line
+        let with = [
+            (b.expr.is_some(), "--expr"),
+            (b.fuzzy.is_some(), "--fuzzy"),
+            (b.multiline.is_some(), "--multiline"),
+            (b.follow.is_some(), "--follow"),
+        ]
+        .into_iter()
+        .find(|(set, _)| *set);
+        if let Some((_, with)) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--searcher"),
+                with: String::from(with),
+            });
+        }
+    }
+    let table = match (b.csv, b.tsv) {
+        (Some(_), Some(position)) => {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--tsv"),
+                with: String::from("--csv"),
+            })
+        }
+        (Some(position), None) => Some((position, ',')),
+        (None, Some(position)) => Some((position, '\t')),
+        (None, None) => None,
+    };
+    if table.is_none() {
+        let companion = match (&b.field, &b.select) {
+            (Some((position, _)), _) => Some((*position, "--field")),
+            (_, Some((position, _))) => Some((*position, "--select")),
+            _ => None,
+        };
+        if let Some((position, flag)) = companion {
+            return Err(ArgError::Requires {
+                position,
+                flag: String::from(flag),
+                requires: String::from("--csv/--tsv"),
+            });
+        }
+    }
+    if let Some((position, delimiter)) = table {
+        // records are printed whole, and can span many lines: there are no lines to number columns in,
+        // nor to replace or show around them
+        let with = [
+            (b.json, "--json"),
+            (b.replace.is_some(), "--replace"),
+            (b.column, "--column"),
+            (b.context > 0, "--context"),
+            (b.multiline.is_some(), "--multiline"),
+            (b.expr.is_some(), "--expr"),
+            (b.fuzzy.is_some(), "--fuzzy"),
+            (b.follow.is_some(), "--follow"),
+            (b.searcher.is_some(), "--searcher"),
+        ]
+        .into_iter()
+        .find(|(set, _)| *set);
+        if let Some((_, with)) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from(if delimiter == ',' { "--csv" } else { "--tsv" }),
+                with: String::from(with),
+            });
+        }
+    }
+
+    if let Some(position) = b.interactive {
+        // the UI searches for one pattern, typed, and only shows the matches
+        let with = [
+            (!patterns.is_empty() || !b.pattern_files.is_empty(), "-e/-f"),
+            (b.json, "--json"),
+            (b.replace.is_some(), "--replace"),
+            (b.context > 0, "--context"),
+            (b.expr.is_some(), "--expr"),
+            (b.fuzzy.is_some(), "--fuzzy"),
+            (b.multiline.is_some(), "--multiline"),
+            (b.follow.is_some(), "--follow"),
+            (b.searcher.is_some(), "--searcher"),
+            (table.is_some(), "--csv/--tsv"),
+            (b.index.is_some(), "--index"),
+            (b.search_zip, "--search-zip"),
+        ]
+        .into_iter()
+        .find(|(set, _)| *set);
+        if let Some((_, with)) = with {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--interactive"),
+                with: String::from(with),
+            });
+        }
+    }
+
+    let mut query = None;
+    if let Some(position) = b.expr {
+        // the expression is the QUERY positional: there is nothing to combine "-e" and "-f" patterns with
+        let Some(query_position) = query_position else {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--expr"),
+                with: String::from("-e/-f"),
+            });
+        };
+        // a file without any of the terms can still match "NOT x": the index can't rule it out
+        if b.index.is_some() {
+            return Err(ArgError::Conflicts {
+                position,
+                flag: String::from("--expr"),
+                with: String::from("--index"),
+            });
+        }
+        let q = expr::parse(&patterns[0]).map_err(|error| ArgError::InvalidExpression {
+            position: query_position,
+            query: patterns[0].clone(),
+            error,
+        })?;
+        patterns = q.terms.clone();
+        query = Some(q);
+    }
+
+    Ok(Command::Search(Box::new(Config {
+        patterns,
+        pattern_files: b.pattern_files,
+        files,
+        case: b.case,
+        line_number: b.line_number,
+        max_count: b.max_count,
+        threads: b.threads,
+        sort: b.sort,
+        context: b.context,
+        json: b.json,
+        replace: b.replace,
+        in_place: b.in_place.is_some(),
+        backup: b.backup.is_some(),
+        normalize: b.normalize.map(|(_, n)| n),
+        column: b.column,
+        text: b.text,
+        encoding: b.encoding,
+        search_zip: b.search_zip,
+        index: b.index.is_some(),
+        expr: query,
+        paragraph: b.paragraph.is_some(),
+        fuzzy: b.fuzzy.map(|(_, k)| k),
+        multiline: b.multiline.is_some(),
+        follow: b.follow.is_some(),
+        searcher: b.searcher.map(|(_, s)| s),
+        table: table.map(|(_, delimiter)| Table {
+            delimiter,
+            field: b.field.map(|(_, c)| c),
+            select: b.select.map_or(vec![], |(_, s)| s),
+        }),
+        interactive: b.interactive.is_some(),
+    })))
+}
+
+fn parse_index(args: &[String]) -> Result<Command, ArgError> {
+    let mut dirs = vec![];
+    for (position, arg) in args.iter().enumerate().skip(2) {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            a if a.starts_with('-') => {
+                return Err(ArgError::UnknownFlag {
+                    position,
+                    flag: arg.clone(),
+                })
+            }
+            _ => dirs.push(arg.clone()),
+        }
+    }
+    if dirs.is_empty() {
+        return Err(ArgError::MissingDirectory);
+    }
+    Ok(Command::Index(dirs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(a: &[&str]) -> Vec<String> {
+        std::iter::once("minigrep")
+            .chain(a.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    fn config(a: &[&str]) -> Config {
+        match parse(&args(a)) {
+            Ok(Command::Search(c)) => *c,
+            Ok(_) => panic!("expected a search"),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn positionals() {
+        let c = config(&["me", "poem.txt"]);
+        assert_eq!(vec!["me"], c.patterns);
+        assert_eq!(vec!["poem.txt"], c.files);
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        let c = config(&["-sn", "me", "poem.txt"]);
+        assert_eq!(CaseMode::Sensitive, c.case);
+        assert!(c.line_number);
+    }
+
+    #[test]
+    fn flag_values() {
+        assert_eq!(Some(3), config(&["--max-count=3", "me", "f"]).max_count);
+        assert_eq!(Some(3), config(&["--max-count", "3", "me", "f"]).max_count);
+        assert_eq!(Some(3), config(&["-nm3", "me", "f"]).max_count);
+        assert_eq!(Some(3), config(&["-m", "3", "me", "f"]).max_count);
+    }
+
+    #[test]
+    fn many_files() {
+        let c = config(&["-j", "4", "--sort", "path", "me", "a.log", "b.log"]);
+        assert_eq!(vec!["a.log", "b.log"], c.files);
+        assert_eq!(4, c.threads);
+        assert_eq!(SortBy::Path, c.sort);
+    }
+
+    #[test]
+    fn index_subcommand() {
+        match parse(&args(&["index", "logs", "archive"])) {
+            Ok(Command::Index(dirs)) => assert_eq!(vec!["logs", "archive"], dirs),
+            _ => panic!("expected the index subcommand"),
+        }
+        assert_eq!(
+            Err(ArgError::MissingDirectory),
+            parse(&args(&["index"])).map(|_| ())
+        );
+        // only as the first argument
+        assert_eq!(vec!["index"], config(&["-i", "index", "f"]).patterns);
+        assert!(config(&["--index", "bryto", "logs"]).index);
+    }
+
+    #[test]
+    fn double_dash_stops_flags() {
+        let c = config(&["-i", "--", "-n", "poem.txt"]);
+        assert_eq!(vec!["-n"], c.patterns);
+        assert!(!c.line_number);
+    }
+
+    #[test]
+    fn smart_case() {
+        let c = config(&["--smart-case", "me", "f"]);
+        assert!(!c.case.is_sensitive(&c.patterns));
+        let c = config(&["-S", "-e", "me", "-e", "Me", "f"]);
+        assert!(c.case.is_sensitive(&c.patterns));
+    }
+
+    #[test]
+    fn repeated_patterns() {
+        let c = config(&[
+            "-e",
+            "N123AB",
+            "--regexp=N456CD",
+            "-f",
+            "tails.txt",
+            "log.txt",
+        ]);
+        assert_eq!(vec!["N123AB", "N456CD"], c.patterns);
+        assert_eq!(vec!["tails.txt"], c.pattern_files);
+        assert_eq!(vec!["log.txt"], c.files);
+    }
+
+    #[test]
+    fn errors_point_at_the_argument() {
+        assert_eq!(
+            Err(ArgError::UnknownFlag {
+                position: 2,
+                flag: String::from("-x")
+            }),
+            parse(&args(&["me", "-ix", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::InvalidValue {
+                position: 2,
+                flag: String::from("--max-count"),
+                value: String::from("many")
+            }),
+            parse(&args(&["--max-count", "many", "me", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::InvalidValue {
+                position: 1,
+                flag: String::from("--sort"),
+                value: String::from("size")
+            }),
+            parse(&args(&["--sort=size", "me", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::MissingFilename),
+            parse(&args(&["me"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::Requires {
+                position: 1,
+                flag: String::from("--in-place"),
+                requires: String::from("--replace")
+            }),
+            parse(&args(&["--in-place", "me", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 3,
+                flag: String::from("--in-place"),
+                with: String::from("--json")
+            }),
+            parse(&args(&[
+                "--json",
+                "--replace=BRYTO",
+                "--in-place",
+                "BRITO",
+                "f"
+            ]))
+            .map(|_| ())
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let c = config(&[
+            "--expr",
+            "--paragraph",
+            "fuel AND (low OR \"engine fire\")",
+            "f",
+        ]);
+        assert_eq!(vec!["fuel", "low", "engine fire"], c.patterns);
+        assert!(c.expr.is_some() && c.paragraph);
+
+        match parse(&args(&["--expr", "fuel AND", "f"])) {
+            Err(ArgError::InvalidExpression {
+                position, error, ..
+            }) => assert_eq!((2, 8), (position, error.position)),
+            _ => panic!("expected an invalid expression"),
+        }
+        assert_eq!(
+            Err(ArgError::Requires {
+                position: 1,
+                flag: String::from("--paragraph"),
+                requires: String::from("--expr")
+            }),
+            parse(&args(&["--paragraph", "me", "f"])).map(|_| ())
+        );
+        assert!(matches!(
+            parse(&args(&["--expr", "-e", "me", "f"])),
+            Err(ArgError::Conflicts { position: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(Some(1), config(&["--fuzzy", "1", "BRYTO", "f"]).fuzzy);
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 1,
+                flag: String::from("--fuzzy"),
+                with: String::from("--index")
+            }),
+            parse(&args(&["--fuzzy=1", "--index", "BRYTO", "logs"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn index_conflicts() {
+        assert!(config(&["--index", "--encoding", "auto", "x", "logs"]).index);
+        for flag in ["--normalize=nfc", "--encoding=latin1"] {
+            assert_eq!(
+                Err(ArgError::Conflicts {
+                    position: 2,
+                    flag: String::from("--index"),
+                    with: String::from(&flag[..flag.find('=').unwrap()])
+                }),
+                parse(&args(&[flag, "--index", "x", "logs"])).map(|_| ())
+            );
+        }
+    }
+
+    #[test]
+    fn multiline() {
+        assert!(config(&["-Un", "Error\\n  at", "f"]).multiline);
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 1,
+                flag: String::from("--multiline"),
+                with: String::from("--context")
+            }),
+            parse(&args(&["-U", "-C2", "x", "f"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn follow() {
+        assert!(config(&["--follow", "-i", "alert", "sim.log"]).follow);
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 2,
+                flag: String::from("--follow"),
+                with: String::from("--json")
+            }),
+            parse(&args(&["--json", "--follow", "alert", "sim.log"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn searcher() {
+        let c = config(&["-s", "--searcher", "two-way", "BRYTO", "f"]);
+        assert_eq!(Some(Strategy::TwoWay), c.searcher);
+        assert_eq!(
+            Err(ArgError::InvalidValue {
+                position: 1,
+                flag: String::from("--searcher"),
+                value: String::from("kmp")
+            }),
+            parse(&args(&["--searcher=kmp", "BRYTO", "f"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let c = config(&[
+            "--csv",
+            "--field",
+            "remarks",
+            "--select=flight,3",
+            "BRYTO",
+            "f.csv",
+        ]);
+        assert_eq!(
+            Some(Table {
+                delimiter: ',',
+                field: Some(Column::Name(String::from("remarks"))),
+                select: vec![Column::Name(String::from("flight")), Column::Number(3)],
+            }),
+            c.table
+        );
+        assert_eq!(
+            Some('\t'),
+            config(&["--tsv", "x", "f"]).table.map(|t| t.delimiter)
+        );
+        assert_eq!(
+            Err(ArgError::InvalidValue {
+                position: 3,
+                flag: String::from("--field"),
+                value: String::from("0")
+            }),
+            parse(&args(&["--csv", "--field", "0", "x", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::Requires {
+                position: 1,
+                flag: String::from("--select"),
+                requires: String::from("--csv/--tsv")
+            }),
+            parse(&args(&["--select=1", "x", "f"])).map(|_| ())
+        );
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 2,
+                flag: String::from("--tsv"),
+                with: String::from("--csv")
+            }),
+            parse(&args(&["--csv", "--tsv", "x", "f"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn interactive() {
+        let c = config(&["--interactive", "-S", "a.log", "b.log"]);
+        assert!(c.interactive && c.patterns.is_empty());
+        assert_eq!(vec!["a.log", "b.log"], c.files);
+        assert_eq!(
+            Err(ArgError::Conflicts {
+                position: 1,
+                flag: String::from("--interactive"),
+                with: String::from("-e/-f")
+            }),
+            parse(&args(&["--interactive", "-e", "x", "f"])).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn help_lists_every_flag() {
+        let h = help();
+        for f in FLAGS {
+            assert!(h.contains(&format!("--{}", f.long)));
+        }
+        assert!(matches!(parse(&args(&["-h"])), Ok(Command::Help)));
+        assert!(h.contains("minigrep index DIR"));
+        assert!(matches!(parse(&args(&["--version"])), Ok(Command::Version)));
+    }
+}
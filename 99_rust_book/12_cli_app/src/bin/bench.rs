@@ -0,0 +1,158 @@
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use minigrep::{aho_corasick::AhoCorasick, searcher::Strategy};
+
+/*
+ * Compares the throughput of the searchers on generated text:
+ *   cargo run --release -p minigrep --bin bench [MiB]
+ *
+ * Every corpus is searched for a few needles by every strategy, plus the Aho-Corasick automaton
+ * minigrep uses by default. The best of 3 runs is kept, in MB/s (higher is better).
+ * Without --release the numbers are meaningless: nothing gets inlined nor vectorized.
+ */
+
+const RUNS: usize = 3;
+
+fn main() {
+    let mib: usize = env::args()
+        .nth(1)
+        .and_then(|a| a.parse().ok())
+        .unwrap_or(16);
+    let size = mib * 1024 * 1024;
+
+    let corpora = [
+        (
+            "prose",
+            prose(size),
+            vec!["BRYTO", "the", "a much longer needle than usual"],
+        ),
+        (
+            "logs",
+            logs(size),
+            vec!["ALERT", "HDG 27", "sim[4242]: ENGINE FIRE"],
+        ),
+        (
+            "dna",
+            dna(size),
+            vec!["GATTACA", "AC", "ACGTACGTACGTACGTACGTACGTACGTAA"],
+        ),
+    ];
+
+    let mut header = format!("{:8} {:32}", "corpus", "needle");
+    for s in Strategy::ALL {
+        header.push_str(&format!(" {:>9}", s.name()));
+    }
+    header.push_str(&format!(" {:>13}", "aho-corasick"));
+    println!("{}   (MB/s, {} MiB per corpus)", header, mib);
+
+    for (name, content, needles) in &corpora {
+        for needle in needles {
+            let mut row = format!("{:8} {:32}", name, format!("{:?}", needle));
+            let mut expected = None;
+            for s in Strategy::ALL {
+                let searcher = s.build(needle);
+                let (lines, t) = best_of(|| searcher.lines(content).len());
+                // they must all agree, or the comparison is pointless
+                assert_eq!(*expected.get_or_insert(lines), lines, "{}", s.name());
+                row.push_str(&format!(" {:>9.0}", throughput(content.len(), t)));
+            }
+            let ac = AhoCorasick::new(&[needle], false);
+            let (lines, t) = best_of(|| content.lines().filter(|l| ac.is_match(l)).count());
+            assert_eq!(expected, Some(lines), "aho-corasick");
+            row.push_str(&format!(" {:>13.0}", throughput(content.len(), t)));
+            println!("{}", row);
+        }
+    }
+}
+
+fn best_of(f: impl Fn() -> usize) -> (usize, Duration) {
+    let mut best = Duration::MAX;
+    let mut res = 0;
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        res = f();
+        best = best.min(started.elapsed());
+    }
+    (res, best)
+}
+
+fn throughput(bytes: usize, t: Duration) -> f64 {
+    bytes as f64 / 1e6 / t.as_secs_f64()
+}
+
+// xorshift64: good enough to make up text, and no dependency needed
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as usize
+    }
+
+    fn pick<'a>(&mut self, words: &[&'a str]) -> &'a str {
+        words[self.next() % words.len()]
+    }
+}
+
+// English-ish lines, where a needle is found on a few of them
+fn prose(size: usize) -> String {
+    const WORDS: &[&str] = &[
+        "the", "of", "and", "to", "in", "is", "you", "that", "it", "he", "was", "for", "on", "are",
+        "as", "with", "his", "they", "at", "be", "this", "have", "from", "or", "one", "had", "by",
+        "word", "but", "not", "what", "all", "were", "we", "when", "your", "can", "said", "there",
+        "nobody", "frog", "public", "dreary", "somebody", "june", "bog", "admiring", "BRYTO",
+    ];
+    let mut r = Random(0x9e37_79b9_7f4a_7c15);
+    let mut out = String::with_capacity(size + 100);
+    while out.len() < size {
+        let words = 6 + r.next() % 10;
+        for i in 0..words {
+            if i > 0 {
+                out.push(' ');
+            }
+            out.push_str(r.pick(WORDS));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// lines like the ones of the flight simulator logs
+fn logs(size: usize) -> String {
+    const EVENTS: &[&str] = &["ALT", "HDG", "SPD", "VS", "FUEL", "ALERT", "ENGINE FIRE"];
+    let mut r = Random(0xdead_beef_cafe_f00d);
+    let mut out = String::with_capacity(size + 100);
+    let mut t = 0;
+    while out.len() < size {
+        t += r.next() % 1000;
+        out.push_str(&format!(
+            "2024-05-01T{:02}:{:02}:{:02}.{:03}Z sim[{}]: {} {}\n",
+            t / 3_600_000 % 24,
+            t / 60_000 % 60,
+            t / 1000 % 60,
+            t % 1000,
+            4000 + r.next() % 400,
+            r.pick(EVENTS),
+            r.next() % 400
+        ));
+    }
+    out
+}
+
+// 4 letters only: lots of partial matches, the worst case for the naive searches
+fn dna(size: usize) -> String {
+    let mut r = Random(0x0123_4567_89ab_cdef);
+    let mut out = String::with_capacity(size + 100);
+    while out.len() < size {
+        for _ in 0..80 {
+            out.push(['A', 'C', 'G', 'T'][r.next() % 4]);
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,263 @@
+use std::{error::Error, fmt};
+
+use crate::Column;
+
+/*
+ * "--csv" and "--tsv" read the files as tables (RFC 4180), so that a search can be limited to one
+ * column ("--field") and print only some of them ("--select"):
+ * - records are separated by "\n" or "\r\n", fields by ',' (or a tab)
+ * - a field between double quotes can contain the separator, newlines and quotes, written twice: "say ""hi"""
+ * - the first record is the header, with the names of the columns
+ *
+ * A record can span many lines, which is why the file is parsed as a whole instead of line by line.
+ */
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    // the line where the record starts, from 1
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CsvError {
+    pub line: usize,
+    // in chars, from 1
+    pub column: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl Error for CsvError {}
+
+pub fn parse(text: &str, delimiter: char) -> Result<Vec<Record>, CsvError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut records = vec![];
+    let mut fields = vec![];
+    let mut field = String::new();
+    let (mut line, mut column) = (1, 1);
+    let mut record_line = 1;
+    // a record with a quoted field isn't a blank line, even if it is just ""
+    let mut quoted = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' && field.is_empty() {
+            // a quoted field: everything up to the closing quote, newlines included
+            let (open_line, open_column) = (line, column);
+            quoted = true;
+            i += 1;
+            column += 1;
+            loop {
+                match chars.get(i) {
+                    None => {
+                        return Err(CsvError {
+                            line: open_line,
+                            column: open_column,
+                            message: "quoted field never closed",
+                        })
+                    }
+                    Some('"') if chars.get(i + 1) == Some(&'"') => {
+                        field.push('"');
+                        i += 2;
+                        column += 2;
+                    }
+                    Some('"') => {
+                        i += 1;
+                        column += 1;
+                        break;
+                    }
+                    Some(&c) => {
+                        field.push(c);
+                        i += 1;
+                        if c == '\n' {
+                            line += 1;
+                            column = 1;
+                        } else {
+                            column += 1;
+                        }
+                    }
+                }
+            }
+            match chars.get(i) {
+                None | Some('\n') => {}
+                Some('\r') if chars.get(i + 1) == Some(&'\n') => {}
+                Some(&c) if c == delimiter => {}
+                Some(_) => {
+                    return Err(CsvError {
+                        line,
+                        column,
+                        message: "unexpected character after a closing quote",
+                    })
+                }
+            }
+            continue;
+        }
+
+        if c == delimiter {
+            fields.push(std::mem::take(&mut field));
+        } else if c == '\n' || (c == '\r' && chars.get(i + 1) == Some(&'\n')) {
+            if c == '\r' {
+                i += 1;
+            }
+            fields.push(std::mem::take(&mut field));
+            // blank lines are skipped
+            if fields.len() > 1 || !fields[0].is_empty() || quoted {
+                records.push(Record {
+                    line: record_line,
+                    fields: std::mem::take(&mut fields),
+                });
+            }
+            fields.clear();
+            quoted = false;
+            line += 1;
+            column = 0;
+            record_line = line;
+        } else {
+            field.push(c);
+        }
+        i += 1;
+        column += 1;
+    }
+    // the last record, when the text doesn't end with a newline
+    if !field.is_empty() || !fields.is_empty() || quoted {
+        fields.push(field);
+        records.push(Record {
+            line: record_line,
+            fields,
+        });
+    }
+    Ok(records)
+}
+
+// the opposite of parse: the fields are quoted only when they need to
+pub fn write(fields: &[&str], delimiter: char) -> String {
+    let quoted: Vec<String> = fields
+        .iter()
+        .map(|f| {
+            if f.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.to_string()
+            }
+        })
+        .collect();
+    quoted.join(&delimiter.to_string())
+}
+
+// the index of a column: by name in the header, or by number (from 1)
+pub fn resolve(header: &[String], column: &Column) -> Result<usize, String> {
+    match column {
+        Column::Name(name) => header
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| format!("no column named {:?}", name)),
+        Column::Number(n) if (1..=header.len()).contains(n) => Ok(n - 1),
+        Column::Number(n) => Err(format!("no column {}, there are {}", n, header.len())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fields(records: &[Record]) -> Vec<Vec<&str>> {
+        records
+            .iter()
+            .map(|r| r.fields.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn quoted_fields() {
+        let text = "flight,remarks,alt\r\nDA123,\"DCT BRYTO, then \"\"FL350\"\"\",350\r\n\nDA456,\"two\nlines\",\n";
+        let records = parse(text, ',').unwrap();
+        assert_eq!(
+            vec![
+                vec!["flight", "remarks", "alt"],
+                vec!["DA123", "DCT BRYTO, then \"FL350\"", "350"],
+                vec!["DA456", "two\nlines", ""],
+            ],
+            fields(&records)
+        );
+        assert_eq!(
+            vec![1, 2, 4],
+            records.iter().map(|r| r.line).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn tabs() {
+        let records = parse("a\tb\n1,5\t2", '\t').unwrap();
+        assert_eq!(vec![vec!["a", "b"], vec!["1,5", "2"]], fields(&records));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Err(CsvError {
+                line: 2,
+                column: 3,
+                message: "quoted field never closed"
+            }),
+            parse("a,b\n1,\"oops\n", ',')
+        );
+        assert_eq!(
+            Err(CsvError {
+                line: 1,
+                column: 4,
+                message: "unexpected character after a closing quote"
+            }),
+            parse("\"a\"b,c", ',')
+        );
+        // a '\r' is only the end of a record with a '\n' after it
+        assert_eq!(
+            Err(CsvError {
+                line: 1,
+                column: 4,
+                message: "unexpected character after a closing quote"
+            }),
+            parse("\"a\"\rb", ',')
+        );
+    }
+
+    #[test]
+    fn quotes_at_the_end_of_records() {
+        let records = parse("a,\"b\"\r\n\"\"\r\n\n\"c\"\r\n\"\"", ',').unwrap();
+        assert_eq!(
+            vec![vec!["a", "b"], vec![""], vec!["c"], vec![""]],
+            fields(&records)
+        );
+        assert_eq!(
+            vec![1, 2, 4, 5],
+            records.iter().map(|r| r.line).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let row = ["DA123", "DCT BRYTO, \"FL350\"", "two\nlines", ""];
+        let text = write(&row, ',');
+        assert_eq!("DA123,\"DCT BRYTO, \"\"FL350\"\"\",\"two\nlines\",", text);
+        assert_eq!(vec![row.to_vec()], fields(&parse(&text, ',').unwrap()));
+    }
+
+    #[test]
+    fn columns() {
+        let header = vec![String::from("flight"), String::from("alt")];
+        assert_eq!(Ok(1), resolve(&header, &Column::Name(String::from("alt"))));
+        assert_eq!(Ok(0), resolve(&header, &Column::Number(1)));
+        assert!(resolve(&header, &Column::Number(3)).is_err());
+        assert!(resolve(&header, &Column::Name(String::from("tail"))).is_err());
+    }
+}
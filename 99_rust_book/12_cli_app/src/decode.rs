@@ -0,0 +1,190 @@
+use std::char::REPLACEMENT_CHARACTER;
+
+use crate::Encoding;
+
+/*
+ * Files are read as bytes and turned into a String here, instead of using fs::read_to_string
+ * (which fails on anything that is not valid UTF-8):
+ * - a BOM (byte order mark) at the start tells us the file is UTF-8, UTF-16LE or UTF-16BE
+ * - without a BOM the file is assumed to be UTF-8, unless "--encoding" says otherwise
+ * - the bytes that aren't valid in the encoding become U+FFFD, the replacement character
+ *
+ * A NUL byte almost never shows up in text, so a file containing one is considered binary: like grep,
+ * we then only tell whether it matches, as printing its "lines" would mess up the terminal.
+ */
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    // the encoding actually used, never Auto
+    pub encoding: Encoding,
+    pub bom: bool,
+    // some bytes weren't valid and have been replaced: writing the text back would lose them
+    pub lossy: bool,
+    // offset of the first NUL, in the decoded text
+    pub binary_offset: Option<usize>,
+}
+
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Decoded {
+    let (encoding, bom) = detect(bytes, encoding);
+    let bytes = &bytes[bom_len(encoding, bom)..];
+
+    let (text, lossy) = match encoding {
+        Encoding::Utf16Le => utf16(bytes, u16::from_le_bytes),
+        Encoding::Utf16Be => utf16(bytes, u16::from_be_bytes),
+        // every byte is a char in Latin-1: the first 256 Unicode code points are Latin-1
+        Encoding::Latin1 => (bytes.iter().map(|&b| b as char).collect(), false),
+        Encoding::Utf8 | Encoding::Auto => match String::from_utf8_lossy(bytes) {
+            std::borrow::Cow::Borrowed(s) => (s.to_string(), false),
+            std::borrow::Cow::Owned(s) => (s, true),
+        },
+    };
+
+    Decoded {
+        binary_offset: text.find('\0'),
+        text,
+        encoding,
+        bom,
+        lossy,
+    }
+}
+
+// the encoding of a file starting with "bytes" (never Auto), and whether it starts with a BOM
+pub fn detect(bytes: &[u8], encoding: Encoding) -> (Encoding, bool) {
+    match encoding {
+        Encoding::Auto => sniff(bytes),
+        e => {
+            let bom = match e {
+                Encoding::Utf8 => UTF8_BOM,
+                Encoding::Utf16Le => UTF16LE_BOM,
+                Encoding::Utf16Be => UTF16BE_BOM,
+                _ => &[],
+            };
+            (e, !bom.is_empty() && bytes.starts_with(bom))
+        }
+    }
+}
+
+pub fn bom_len(encoding: Encoding, bom: bool) -> usize {
+    match (bom, encoding) {
+        (false, _) => 0,
+        (true, Encoding::Utf8) => UTF8_BOM.len(),
+        (true, _) => UTF16LE_BOM.len(),
+    }
+}
+
+fn sniff(bytes: &[u8]) -> (Encoding, bool) {
+    if bytes.starts_with(UTF8_BOM) {
+        (Encoding::Utf8, true)
+    } else if bytes.starts_with(UTF16LE_BOM) {
+        (Encoding::Utf16Le, true)
+    } else if bytes.starts_with(UTF16BE_BOM) {
+        (Encoding::Utf16Be, true)
+    } else {
+        (Encoding::Utf8, false)
+    }
+}
+
+fn utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> (String, bool) {
+    let units = bytes.chunks_exact(2).map(|c| unit([c[0], c[1]]));
+    let mut lossy = false;
+    let mut text: String = char::decode_utf16(units)
+        .map(|r| {
+            r.unwrap_or_else(|_| {
+                lossy = true;
+                REPLACEMENT_CHARACTER
+            })
+        })
+        .collect();
+    // an odd number of bytes: the last one can't be decoded
+    if bytes.len() % 2 == 1 {
+        text.push(REPLACEMENT_CHARACTER);
+        lossy = true;
+    }
+    (text, lossy)
+}
+
+// the opposite of decode, used to write a file back in the encoding it had
+pub fn encode(d: &Decoded, text: &str) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    match d.encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if d.bom {
+                out.extend_from_slice(if d.encoding == Encoding::Utf16Le {
+                    UTF16LE_BOM
+                } else {
+                    UTF16BE_BOM
+                });
+            }
+            for u in text.encode_utf16() {
+                let b = if d.encoding == Encoding::Utf16Le {
+                    u.to_le_bytes()
+                } else {
+                    u.to_be_bytes()
+                };
+                out.extend_from_slice(&b);
+            }
+        }
+        Encoding::Latin1 => {
+            for c in text.chars() {
+                let b = u8::try_from(u32::from(c))
+                    .map_err(|_| format!("'{}' can't be written in Latin-1", c))?;
+                out.push(b);
+            }
+        }
+        Encoding::Utf8 | Encoding::Auto => {
+            if d.bom {
+                out.extend_from_slice(UTF8_BOM);
+            }
+            out.extend_from_slice(text.as_bytes());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn utf16_with_bom() {
+        let mut le = vec![0xFF, 0xFE];
+        let mut be = vec![0xFE, 0xFF];
+        for u in "ALT 350\n".encode_utf16() {
+            le.extend_from_slice(&u.to_le_bytes());
+            be.extend_from_slice(&u.to_be_bytes());
+        }
+
+        let d = decode(&le, Encoding::Auto);
+        assert_eq!(
+            ("ALT 350\n", Encoding::Utf16Le, None),
+            (d.text.as_str(), d.encoding, d.binary_offset)
+        );
+        assert_eq!(le, encode(&d, &d.text).unwrap());
+        let d = decode(&be, Encoding::Auto);
+        assert_eq!(
+            ("ALT 350\n", Encoding::Utf16Be),
+            (d.text.as_str(), d.encoding)
+        );
+    }
+
+    #[test]
+    fn latin1() {
+        let d = decode(b"caf\xe9", Encoding::Latin1);
+        assert_eq!("café", d.text);
+        assert_eq!(b"caf\xe9".to_vec(), encode(&d, &d.text).unwrap());
+        assert!(encode(&d, "€").is_err());
+        // the same bytes are not valid UTF-8
+        assert!(decode(b"caf\xe9", Encoding::Auto).lossy);
+    }
+
+    #[test]
+    fn binary() {
+        let d = decode(b"ELF\0\x01\x02", Encoding::Auto);
+        assert_eq!(Some(3), d.binary_offset);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{error::Error, fmt};
+
+/*
+ * Boolean query expressions, e.g. (fuel AND low) OR "engine fire" NOT test
+ *
+ * - a term is a word, or a phrase between double quotes: it matches when it is found in the text
+ * - NOT binds the tightest, then AND, then OR: the query above is (fuel AND low) OR ("engine fire" AND NOT test)
+ * - two terms next to each other are joined by an implicit AND
+ * - the operators must be uppercase: "and" is just a word to search for
+ *
+ * The grammar, parsed top-down by one function per rule (a recursive descent parser):
+ *   or      := and ("OR" and)*
+ *   and     := unary ("AND"? unary)*
+ *   unary   := "NOT" unary | primary
+ *   primary := WORD | PHRASE | "(" or ")"
+ */
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    // index into Query::terms
+    Term(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    // "found" tells, for every term, whether it is in the text being evaluated
+    pub fn eval(&self, found: &[bool]) -> bool {
+        match self {
+            Expr::Term(t) => found[*t],
+            Expr::Not(e) => !e.eval(found),
+            Expr::And(a, b) => a.eval(found) && b.eval(found),
+            Expr::Or(a, b) => a.eval(found) || b.eval(found),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    // as the user wrote it
+    pub text: String,
+    pub expr: Expr,
+    // every distinct term, searched all at once
+    pub terms: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ExprError {
+    // in chars, starting from 0
+    pub position: usize,
+    pub message: String,
+}
+
+impl ExprError {
+    // the query with a caret under the position at fault
+    pub fn show(&self, query: &str) -> String {
+        format!(
+            "{}\n    {}\n    {}^",
+            self,
+            query,
+            " ".repeat(self.position)
+        )
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position + 1)
+    }
+}
+
+impl Error for ExprError {}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn describe(t: &Token) -> String {
+    match t {
+        Token::Term(s) => format!("term {:?}", s),
+        Token::And => String::from("AND"),
+        Token::Or => String::from("OR"),
+        Token::Not => String::from("NOT"),
+        Token::Open => String::from("'('"),
+        Token::Close => String::from("')'"),
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push((start, Token::Open));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((start, Token::Close));
+                i += 1;
+            }
+            '"' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or(ExprError {
+                        position: start,
+                        message: String::from("unterminated phrase"),
+                    })?;
+                let phrase: String = chars[i + 1..i + 1 + end].iter().collect();
+                if phrase.is_empty() {
+                    return Err(ExprError {
+                        position: start,
+                        message: String::from("empty phrase"),
+                    });
+                }
+                tokens.push((start, Token::Term(phrase)));
+                i += end + 2;
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !"()\"".contains(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Term(word),
+                };
+                tokens.push((start, token));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    // where the query ends, for the errors about something missing
+    end: usize,
+    terms: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error(&self, message: String) -> ExprError {
+        ExprError {
+            position: self.position(),
+            message,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                // implicit AND
+                Some(Token::Term(_)) | Some(Token::Not) | Some(Token::Open) => {}
+                _ => return Ok(left),
+            }
+            let right = self.unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error(String::from("expected a term")));
+        };
+        match token {
+            Token::Term(t) => {
+                self.pos += 1;
+                let id = match self.terms.iter().position(|x| *x == t) {
+                    Some(id) => id,
+                    None => {
+                        self.terms.push(t);
+                        self.terms.len() - 1
+                    }
+                };
+                Ok(Expr::Term(id))
+            }
+            Token::Open => {
+                let open = self.position();
+                self.pos += 1;
+                let e = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(ExprError {
+                        position: open,
+                        message: String::from("'(' is never closed"),
+                    });
+                }
+                self.pos += 1;
+                Ok(e)
+            }
+            t => Err(self.error(format!("expected a term, found {}", describe(&t)))),
+        }
+    }
+}
+
+pub fn parse(query: &str) -> Result<Query, ExprError> {
+    let mut p = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+        end: query.chars().count(),
+        terms: vec![],
+    };
+    let expr = p.or()?;
+    if let Some(t) = p.peek() {
+        let message = format!("unexpected {}", describe(t));
+        return Err(p.error(message));
+    }
+    Ok(Query {
+        text: query.to_string(),
+        expr,
+        terms: p.terms,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(query: &str, text: &str) -> bool {
+        let q = parse(query).unwrap();
+        let found: Vec<bool> = q.terms.iter().map(|t| text.contains(t.as_str())).collect();
+        q.expr.eval(&found)
+    }
+
+    #[test]
+    fn precedence() {
+        let q = r#"(fuel AND low) OR "engine fire" NOT test"#;
+        assert!(eval(q, "fuel is low"));
+        assert!(eval(q, "engine fire on 2"));
+        assert!(!eval(q, "engine fire test"));
+        assert!(!eval(q, "fuel ok"));
+        assert!(eval("a OR b c", "b c"));
+        assert!(!eval("a OR b c", "b"));
+        assert!(eval("NOT NOT a", "a"));
+    }
+
+    #[test]
+    fn same_term_once() {
+        assert_eq!(vec!["a", "b"], parse("a AND (b OR a)").unwrap().terms);
+    }
+
+    #[test]
+    fn errors_point_at_the_position() {
+        let err = |q| parse(q).unwrap_err();
+        assert_eq!(14, err("(fuel AND low)) OR x").position);
+        assert_eq!(0, err("(fuel AND low").position);
+        assert_eq!(9, err("fuel AND ").position);
+        assert_eq!(5, err("fuel \"engine").position);
+        assert_eq!(0, err("OR x").position);
+        assert_eq!(
+            "unexpected ')' at position 15\n    (fuel AND low)) OR x\n                  ^",
+            err("(fuel AND low)) OR x").show("(fuel AND low)) OR x")
+        );
+    }
+}
@@ -0,0 +1,81 @@
+mod table;
+
+/*
+ * Case folding maps the chars that only differ by case to the same thing, so that comparing the folded
+ * strings is a case insensitive comparison. It is not the same as lowercasing:
+ * - 'ß'.to_lowercase() is 'ß' and "SS".to_lowercase() is "ss": they don't match, but they should.
+ *   Full folding turns 'ß' into "ss" (one char can become many).
+ * - the Greek final sigma 'ς' lowercases to itself, but folds to 'σ' like 'Σ' does.
+ *
+ * "full" is what the searches use. "simple" never changes the number of chars (one char in, one char
+ * out), which is handy when positions in chars must be preserved, at the cost of missing 'ß' vs "SS".
+ */
+
+pub enum Folded {
+    One(Option<char>),
+    Many(std::str::Chars<'static>),
+}
+
+impl Iterator for Folded {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self {
+            Folded::One(c) => c.take(),
+            Folded::Many(chars) => chars.next(),
+        }
+    }
+}
+
+pub fn full(c: char) -> Folded {
+    // the fast path: most of the text we search is ASCII
+    if c.is_ascii() {
+        return Folded::One(Some(c.to_ascii_lowercase()));
+    }
+    match table::FULL.binary_search_by_key(&c, |&(k, _)| k) {
+        Ok(i) => Folded::Many(table::FULL[i].1.chars()),
+        Err(_) => Folded::One(Some(c)),
+    }
+}
+
+pub fn simple(c: char) -> char {
+    let mut f = full(c);
+    if let (Some(first), None) = (f.next(), f.next()) {
+        return first;
+    }
+    // the chars with no one-to-one folding, e.g. 'ẞ' (whose full folding is "ss") becomes 'ß'
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
+
+pub fn fold_str(s: &str) -> String {
+    s.chars().flat_map(full).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn table_is_sorted() {
+        assert!(table::FULL.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn full_folding() {
+        assert_eq!(fold_str("STRASSE"), fold_str("straße"));
+        assert_eq!(fold_str("ΣΟΦΟΣ"), fold_str("σοφος"));
+        assert_eq!("ﬁ", "ﬁ".to_lowercase());
+        assert_eq!("fi", fold_str("ﬁ"));
+    }
+
+    #[test]
+    fn simple_folding() {
+        assert_eq!('ß', simple('ẞ'));
+        assert_eq!('σ', simple('ς'));
+        assert_eq!('k', simple('K'));
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use crate::{decode, Encoding};
+
+/*
+ * "--follow" watches files the way "tail -F" does, by polling them: every so often the file we have
+ * open is checked for new data, and the path for a different file.
+ * - appended: the file grew, the new complete lines are returned
+ * - truncated: the file is smaller than what we have read, it is read again from the start
+ * - rotated: the path now leads to another file (e.g. "sim.log" was renamed to "sim.log.1" and a new
+ *   "sim.log" was created). What was still unread in the old file is read, then we switch to the new one.
+ * - missing: the path doesn't exist (yet, or in the middle of a rotation), we keep waiting for it
+ *
+ * A file is recognized by its device and inode numbers, which a rename doesn't change. They only
+ * exist on Unix: elsewhere a rotation is only noticed when the file at the path is smaller than what
+ * we have read of the open one.
+ *
+ * The lines are decoded like the searched files are (see decode.rs): the BOM at the start of the file
+ * says which encoding it has, unless "--encoding" does. In UTF-16 a '\n' is 2 bytes, so the lines are
+ * split on whole code units.
+ */
+
+// how much of the file is read at once, when looking for the lines before the followed ones
+const BLOCK: usize = 64 * 1024;
+
+pub struct Follower {
+    pub path: PathBuf,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    // the one asked for, and the one of the open file (never Auto)
+    encoding: Encoding,
+    file_encoding: Encoding,
+    // bytes read from the open file
+    pos: u64,
+    // the last line, not complete yet
+    partial: Vec<u8>,
+    // number of the last complete line
+    line_number: usize,
+}
+
+// what a poll found: a message for the user, if something happened to the file, and the new lines
+pub struct Poll {
+    pub notice: Option<&'static str>,
+    pub lines: Vec<(usize, String)>,
+}
+
+impl Follower {
+    /*
+     * Only the lines added from now on are returned: the file is opened at the start of its last line.
+     * That one is found by reading the file backwards from its end, unless the lines must be numbered
+     * ("count_lines"): they are then counted from the start, one block at a time. Without it, the
+     * numbers only count the lines read since.
+     */
+    pub fn new(path: PathBuf, encoding: Encoding, count_lines: bool) -> io::Result<Follower> {
+        let mut f = Follower {
+            path,
+            file: None,
+            id: None,
+            encoding,
+            file_encoding: encoding,
+            pos: 0,
+            partial: vec![],
+            line_number: 0,
+        };
+        match File::open(&f.path) {
+            Ok(mut file) => {
+                let mut head = vec![];
+                (&mut file).take(3).read_to_end(&mut head)?;
+                let (encoding, bom) = decode::detect(&head, f.encoding);
+                f.file_encoding = encoding;
+                let start = decode::bom_len(encoding, bom) as u64;
+                let nl = newline(encoding);
+                if count_lines {
+                    (f.line_number, f.pos) = count(&mut file, start, nl)?;
+                } else {
+                    f.pos = last_line_start(&mut file, start, nl)?;
+                }
+                file.seek(SeekFrom::Start(f.pos))?;
+                f.id = identity(&file.metadata()?);
+                f.file = Some(file);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(f)
+    }
+
+    pub fn poll(&mut self) -> io::Result<Poll> {
+        let mut notice = None;
+        let mut lines = vec![];
+
+        if let Some(file) = &mut self.file {
+            if file.metadata()?.len() < self.pos {
+                file.seek(SeekFrom::Start(0))?;
+                self.restart();
+                notice = Some("file truncated");
+            }
+            self.read(&mut lines)?;
+        }
+
+        match fs::metadata(&self.path) {
+            Ok(meta) if self.file.is_none() || self.replaced(&meta) => {
+                // a last line with no '\n' won't be completed anymore
+                self.flush(&mut lines);
+                notice = Some(if self.file.is_some() {
+                    "file replaced, following the new one"
+                } else {
+                    "file appeared, following it"
+                });
+                let file = File::open(&self.path)?;
+                self.id = identity(&file.metadata()?);
+                self.file = Some(file);
+                self.restart();
+                self.read(&mut lines)?;
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Poll { notice, lines })
+    }
+
+    // whether the file at the path ("meta") isn't the open one anymore
+    fn replaced(&self, meta: &Metadata) -> bool {
+        match self.id {
+            Some(_) => identity(meta) != self.id,
+            None => meta.len() < self.pos,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.pos = 0;
+        self.partial.clear();
+        self.line_number = 0;
+    }
+
+    fn read(&mut self, lines: &mut Vec<(usize, String)>) -> io::Result<()> {
+        let Some(file) = &mut self.file else {
+            return Ok(());
+        };
+        let mut buf = vec![];
+        let read = file.read_to_end(&mut buf)?;
+        // the start of the file (or of the new one): it may have a BOM
+        if self.pos == 0 && read > 0 {
+            let (encoding, bom) = decode::detect(&buf, self.encoding);
+            self.file_encoding = encoding;
+            buf.drain(..decode::bom_len(encoding, bom));
+        }
+        self.pos += read as u64;
+        self.partial.extend(buf);
+        let nl = newline(self.file_encoding);
+        while let Some(end) = line_end(&self.partial, nl) {
+            let line: Vec<u8> = self.partial.drain(..end).collect();
+            self.push(&line[..end - nl.len()], lines);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, lines: &mut Vec<(usize, String)>) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.push(&line, lines);
+        }
+    }
+
+    fn push(&mut self, line: &[u8], lines: &mut Vec<(usize, String)>) {
+        self.line_number += 1;
+        let mut text = decode::decode(line, self.file_encoding).text;
+        if text.ends_with('\r') {
+            text.pop();
+        }
+        lines.push((self.line_number, text));
+    }
+}
+
+fn newline(encoding: Encoding) -> &'static [u8] {
+    match encoding {
+        Encoding::Utf16Le => &[b'\n', 0],
+        Encoding::Utf16Be => &[0, b'\n'],
+        _ => b"\n",
+    }
+}
+
+// the end of the first line in "bytes", which start at the start of a code unit
+fn line_end(bytes: &[u8], nl: &[u8]) -> Option<usize> {
+    let i = bytes.chunks_exact(nl.len()).position(|unit| unit == nl)?;
+    Some((i + 1) * nl.len())
+}
+
+// the number of complete lines after "start", and where the last one ends
+fn count(file: &mut File, start: u64, nl: &[u8]) -> io::Result<(usize, u64)> {
+    file.seek(SeekFrom::Start(start))?;
+    let (mut lines, mut end, mut pos) = (0, start, start);
+    let mut block = vec![];
+    loop {
+        // what is left of the block is half a code unit, read again with the next one
+        let left = block.len();
+        block.resize(left + BLOCK, 0);
+        let read = file.read(&mut block[left..])?;
+        block.truncate(left + read);
+        if read == 0 {
+            return Ok((lines, end));
+        }
+        let units = block.len() / nl.len() * nl.len();
+        for (i, unit) in block[..units].chunks_exact(nl.len()).enumerate() {
+            if unit == nl {
+                lines += 1;
+                end = pos + ((i + 1) * nl.len()) as u64;
+            }
+        }
+        pos += units as u64;
+        block.drain(..units);
+    }
+}
+
+// the start of the last line after "start", reading the file backwards one block at a time
+fn last_line_start(file: &mut File, start: u64, nl: &[u8]) -> io::Result<u64> {
+    let n = nl.len() as u64;
+    let len = file.metadata()?.len().max(start);
+    // BLOCK is even, so every block starts at the start of a code unit too
+    let mut to = start + (len - start) / n * n;
+    let mut block = vec![0; BLOCK];
+    while to > start {
+        let from = to.saturating_sub(BLOCK as u64).max(start);
+        let block = &mut block[..(to - from) as usize];
+        file.seek(SeekFrom::Start(from))?;
+        file.read_exact(block)?;
+        if let Some(i) = block.chunks_exact(nl.len()).rposition(|unit| unit == nl) {
+            return Ok(from + (i as u64 + 1) * n);
+        }
+        to = from;
+    }
+    Ok(start)
+}
+
+#[cfg(unix)]
+fn identity(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &PathBuf, text: &str) {
+        let mut f = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .unwrap();
+        f.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn lines(f: &mut Follower) -> (Option<&'static str>, Vec<(usize, String)>) {
+        let p = f.poll().unwrap();
+        (p.notice, p.lines)
+    }
+
+    #[test]
+    fn appends_truncation_and_rotation() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sim.log");
+        fs::write(&path, "old 1\nold 2\npart").unwrap();
+
+        let mut f = Follower::new(path.clone(), Encoding::Auto, true).unwrap();
+        assert_eq!((None, vec![]), lines(&mut f));
+        append(&path, "ial\nALERT fuel\n");
+        assert_eq!(
+            (
+                None,
+                vec![
+                    (3, String::from("partial")),
+                    (4, String::from("ALERT fuel"))
+                ]
+            ),
+            lines(&mut f)
+        );
+
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(
+            (Some("file truncated"), vec![(1, String::from("new"))]),
+            lines(&mut f)
+        );
+
+        // written just before the rotation: still read from the old file
+        append(&path, "last\n");
+        fs::rename(&path, dir.join("sim.log.1")).unwrap();
+        assert_eq!((None, vec![(2, String::from("last"))]), lines(&mut f));
+        fs::write(&path, "ALERT engine\n").unwrap();
+        let (notice, new) = lines(&mut f);
+        assert!(notice.is_some());
+        assert_eq!(vec![(1, String::from("ALERT engine"))], new);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // what happens where files have no identity
+    #[test]
+    fn rotation_by_size() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-size-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sim.log");
+        fs::write(&path, "old 1\nold 2\n").unwrap();
+        let mut f = Follower::new(path.clone(), Encoding::Auto, true).unwrap();
+        f.id = None;
+
+        fs::rename(&path, dir.join("sim.log.1")).unwrap();
+        append(&path, "new\n");
+        assert_eq!(
+            (
+                Some("file replaced, following the new one"),
+                vec![(1, String::from("new"))]
+            ),
+            lines(&mut f)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encodings() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-enc-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sim.log");
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        // "\u{a0a}" has a '\n' byte in it, which isn't a newline in UTF-16
+        let mut content = vec![0xFF, 0xFE];
+        content.extend(utf16("a\nb\u{a0a}\r\npar"));
+        fs::write(&path, &content).unwrap();
+
+        // the last line is found from the end, without counting the ones before
+        let mut f = Follower::new(path.clone(), Encoding::Auto, false).unwrap();
+        assert_eq!((None, vec![]), lines(&mut f));
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&utf16("tial\nALERT\n")).unwrap();
+        assert_eq!(
+            (
+                None,
+                vec![(1, String::from("partial")), (2, String::from("ALERT"))]
+            ),
+            lines(&mut f)
+        );
+        let f = Follower::new(path.clone(), Encoding::Auto, true).unwrap();
+        assert_eq!(4, f.line_number);
+
+        // latin1 has to be asked for
+        fs::write(&path, b"caf\xe9\n").unwrap();
+        let mut f = Follower::new(path.clone(), Encoding::Latin1, false).unwrap();
+        append(&path, "x\n");
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"na\xefve\n")
+            .unwrap();
+        assert_eq!(
+            vec![(1, String::from("x")), (2, String::from("na\u{ef}ve"))],
+            lines(&mut f).1
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
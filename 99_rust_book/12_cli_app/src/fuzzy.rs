@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use crate::{aho_corasick::Match, fold};
+
+/*
+ * Approximate matching: a pattern is found wherever a substring of the haystack is within K edits
+ * (a char inserted, deleted or substituted) of it, e.g. "BRITO" is 1 edit away from "BRYTO".
+ *
+ * The textbook way computes a table of edit distances, one column per char of the haystack and one row
+ * per char of the pattern. Myers' bit-vector algorithm (1999) stores a whole column in the bits of a u64:
+ * two adjacent cells differ by -1, 0 or +1, so the column is encoded as the bits where it goes up and the
+ * bits where it goes down, and the next column is computed with a handful of bitwise operations.
+ * That's O(haystack) for patterns of up to 64 chars, instead of O(haystack * pattern).
+ *
+ * Distances are counted in chars. With case_insensitive, chars are compared with their simple case
+ * folding, which maps one char to one char and so keeps the positions meaningful.
+ */
+
+const MAX_LEN: usize = 64;
+
+pub struct Fuzzy {
+    patterns: Vec<Vec<char>>,
+    // for every pattern, the bits of the positions where each of its chars appears
+    peq: Vec<HashMap<char, u64>>,
+    k: usize,
+    case_insensitive: bool,
+}
+
+impl Fuzzy {
+    pub fn new<S: AsRef<str>>(
+        patterns: &[S],
+        k: usize,
+        case_insensitive: bool,
+    ) -> Result<Fuzzy, String> {
+        let mut chars = vec![];
+        let mut peq = vec![];
+        for p in patterns {
+            let p = p.as_ref();
+            let folded: Vec<char> = p.chars().map(|c| fold_char(c, case_insensitive)).collect();
+            if folded.len() > MAX_LEN {
+                return Err(format!(
+                    "fuzzy patterns can't be longer than {} chars: {:?}",
+                    MAX_LEN, p
+                ));
+            }
+            // every substring of K chars or less would be a match, the empty one included
+            if folded.len() <= k {
+                return Err(format!(
+                    "--fuzzy {} would match everything: {:?} is only {} chars long",
+                    k,
+                    p,
+                    folded.len()
+                ));
+            }
+            let mut bits: HashMap<char, u64> = HashMap::new();
+            for (i, &c) in folded.iter().enumerate() {
+                *bits.entry(c).or_insert(0) |= 1 << i;
+            }
+            chars.push(folded);
+            peq.push(bits);
+        }
+        Ok(Fuzzy {
+            patterns: chars,
+            peq,
+            k,
+            case_insensitive,
+        })
+    }
+
+    /*
+     * The best match of every pattern in each run of chars where a match ends, so the same occurrence
+     * isn't reported once per possible end. The matches of different patterns can overlap.
+     */
+    pub fn find_iter(&self, haystack: &str) -> Vec<Match> {
+        let text: Vec<(usize, char)> = haystack
+            .char_indices()
+            .map(|(i, c)| (i, fold_char(c, self.case_insensitive)))
+            .collect();
+        let mut res = vec![];
+        for pattern in 0..self.patterns.len() {
+            let scores = self.scores(pattern, &text);
+            let mut j = 0;
+            while j < scores.len() {
+                if scores[j] > self.k {
+                    j += 1;
+                    continue;
+                }
+                // the end with the smallest distance in this run, the last one when tied: with "cafe",
+                // "CAF" is as close as "CAFÉ" is, but it would stop the match in the middle of a word
+                let mut best = j;
+                while j < scores.len() && scores[j] <= self.k {
+                    if scores[j] <= scores[best] {
+                        best = j;
+                    }
+                    j += 1;
+                }
+                let start = self.start(pattern, &text, best, scores[best]);
+                let (last, c) = text[best];
+                res.push(Match {
+                    pattern,
+                    start: text[start].0,
+                    end: last + c.len_utf8(),
+                    distance: scores[best],
+                });
+            }
+        }
+        res.sort_by_key(|m| (m.start, m.pattern));
+        res
+    }
+
+    // for every char of the text, the smallest distance of the pattern to a substring ending there
+    fn scores(&self, pattern: usize, text: &[(usize, char)]) -> Vec<usize> {
+        let m = self.patterns[pattern].len();
+        let peq = &self.peq[pattern];
+        let last = 1u64 << (m - 1);
+        // vertical deltas of the current column: +1 (all of them at first) and -1
+        let (mut pv, mut mv) = (!0u64, 0u64);
+        let mut score = m;
+        let mut scores = Vec::with_capacity(text.len());
+        for &(_, c) in text {
+            let eq = peq.get(&c).copied().unwrap_or(0);
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            // horizontal deltas
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            if ph & last != 0 {
+                score += 1;
+            } else if mh & last != 0 {
+                score -= 1;
+            }
+            // no "| 1" after the shift: a match can start anywhere in the text, for free
+            ph <<= 1;
+            mh <<= 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+            scores.push(score);
+        }
+        scores
+    }
+
+    /*
+     * The bit vectors only tell where matches end. The start is found with the plain table, going
+     * backwards from the end: the match can't be longer than the pattern plus K chars.
+     * The shortest substring at the given distance wins, so a separator next to the match isn't eaten.
+     */
+    fn start(&self, pattern: usize, text: &[(usize, char)], end: usize, distance: usize) -> usize {
+        let p = &self.patterns[pattern];
+        let m = p.len();
+        // column l: distances of the last i chars of the pattern to the last l chars of the text
+        let mut column: Vec<usize> = (0..=m).collect();
+        let longest = (m + self.k).min(end + 1);
+        for l in 1..=longest {
+            let c = text[end + 1 - l].1;
+            let mut next = vec![l; m + 1];
+            for i in 1..=m {
+                let cost = usize::from(p[m - i] != c);
+                next[i] = (column[i - 1] + cost)
+                    .min(column[i] + 1)
+                    .min(next[i - 1] + 1);
+            }
+            column = next;
+            if column[m] == distance {
+                return end + 1 - l;
+            }
+        }
+        // not reached: the distance comes from a substring of at most "longest" chars
+        end + 1 - longest
+    }
+}
+
+fn fold_char(c: char, case_insensitive: bool) -> char {
+    if case_insensitive {
+        fold::simple(c)
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the matches as "text~distance"
+    fn find(patterns: &[&str], k: usize, haystack: &str) -> Vec<String> {
+        let f = Fuzzy::new(patterns, k, true).unwrap();
+        f.find_iter(haystack)
+            .iter()
+            .map(|m| format!("{}~{}", &haystack[m.start..m.end], m.distance))
+            .collect()
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(vec!["BRITO~1"], find(&["BRYTO"], 1, "DCT BRITO CLEARED"));
+        assert_eq!(vec!["bryto~0"], find(&["BRYTO"], 2, "direct bryto"));
+        // a deletion and an insertion
+        assert_eq!(vec!["BRTO~1"], find(&["BRYTO"], 1, "DCT BRTO"));
+        assert_eq!(vec!["BRYYTO~1"], find(&["BRYTO"], 1, "BRYYTO"));
+        assert!(find(&["BRYTO"], 1, "DCT BRIT0").is_empty());
+        assert_eq!(
+            vec!["BRITO~1", "BRUTO~1"],
+            find(&["BRYTO"], 1, "BRITO then BRUTO")
+        );
+    }
+
+    #[test]
+    fn unicode_chars() {
+        // one edit, not the two bytes of 'é'
+        assert_eq!(vec!["CAFÉ~1"], find(&["cafe"], 1, "le CAFÉ"));
+    }
+
+    #[test]
+    fn too_fuzzy() {
+        assert!(Fuzzy::new(&["ab"], 2, false).is_err());
+        assert!(Fuzzy::new(&["x".repeat(65)], 1, false).is_err());
+    }
+}
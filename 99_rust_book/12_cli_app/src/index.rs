@@ -0,0 +1,374 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{archive, decode, fold, replace::write_atomic, Encoding};
+
+/*
+ * A trigram index makes the repeated searches over the same files faster by skipping the files that
+ * can't match: "minigrep index DIR" stores, for every 3 bytes sequence (trigram) found in the files of
+ * DIR, the list of files containing it. A file can only contain "BRYTO" if it contains "bry", "ryt"
+ * and "yto", so "--index" only searches the files found in all three lists.
+ *
+ * The trigrams are taken from the case folded text, so the same index works for case sensitive and
+ * insensitive searches (folding can only add candidates, never remove one). Patterns shorter than 3
+ * bytes have no trigrams: every file is then a candidate.
+ *
+ * Every file is stored with its modification time and size: when they change, the file is indexed again,
+ * when it disappears it is removed. Only those files are read again, not the whole directory.
+ *
+ * The index lives in DIR/.minigrep-index, in a small binary format:
+ *   "MGIDX" version(u8)
+ *   files(u32), then for every file: path length(u16) path mtime_secs(u64) mtime_nanos(u32) size(u64) opaque(u8)
+ *   trigrams(u32), then for every trigram: trigram(u32) files(u32) file ids(u32...)
+ * All the numbers are little endian.
+ */
+
+pub const FILE_NAME: &str = ".minigrep-index";
+const MAGIC: &[u8] = b"MGIDX";
+const VERSION: u8 = 1;
+
+// modification time (seconds, nanoseconds) and size of a file
+type Stamp = ((u64, u32), u64);
+
+#[derive(Debug, Clone, PartialEq)]
+struct FileEntry {
+    // relative to the indexed directory
+    path: String,
+    mtime: (u64, u32),
+    size: u64,
+    // archives can't be indexed without decompressing them: they are always candidates
+    opaque: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    // None for the files removed since the index was loaded: ids must not change until it's saved again
+    files: Vec<Option<FileEntry>>,
+    postings: BTreeMap<u32, Vec<u32>>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Refresh {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+pub fn index_path(dir: &str) -> PathBuf {
+    Path::new(dir).join(FILE_NAME)
+}
+
+// loads DIR's index, brings it up to date with what's on disk and saves it if anything changed
+pub fn update(dir: &str) -> Result<(Index, Refresh), String> {
+    let path = index_path(dir);
+    let mut index = match fs::read(&path) {
+        Ok(bytes) => Index::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Err(_) => Index::default(),
+    };
+    let refresh = index.refresh(dir)?;
+    if refresh.updated > 0 || refresh.removed > 0 {
+        index.compact();
+        write_atomic(&path, &index.to_bytes(), false)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok((index, refresh))
+}
+
+impl Index {
+    pub fn refresh(&mut self, dir: &str) -> Result<Refresh, String> {
+        let mut on_disk = HashMap::new();
+        walk(Path::new(dir), Path::new(""), &mut on_disk)?;
+
+        let mut refresh = Refresh::default();
+        let mut stale: BTreeSet<u32> = BTreeSet::new();
+        for (id, slot) in self.files.iter_mut().enumerate() {
+            let Some(f) = slot else { continue };
+            match on_disk.get(&f.path) {
+                Some(&(mtime, size)) if (mtime, size) == (f.mtime, f.size) => {
+                    on_disk.remove(&f.path);
+                }
+                Some(_) => {
+                    // changed: indexed again below, with a new id
+                    stale.insert(id as u32);
+                    *slot = None;
+                }
+                None => {
+                    stale.insert(id as u32);
+                    *slot = None;
+                    refresh.removed += 1;
+                }
+            }
+        }
+        if !stale.is_empty() {
+            for ids in self.postings.values_mut() {
+                ids.retain(|id| !stale.contains(id));
+            }
+            self.postings.retain(|_, ids| !ids.is_empty());
+        }
+
+        let mut added: Vec<(String, Stamp)> = on_disk.into_iter().collect();
+        added.sort();
+        for (path, (mtime, size)) in added {
+            let full = Path::new(dir).join(&path);
+            let bytes = fs::read(&full).map_err(|e| format!("{}: {}", full.display(), e))?;
+            let opaque = archive::is_archive(&path);
+            let id = self.files.len() as u32;
+            self.files.push(Some(FileEntry {
+                path,
+                mtime,
+                size,
+                opaque,
+            }));
+            if !opaque {
+                let text = decode::decode(&bytes, Encoding::Auto).text;
+                for t in trigrams(&fold::fold_str(&text)) {
+                    self.postings.entry(t).or_default().push(id);
+                }
+            }
+            refresh.updated += 1;
+        }
+
+        refresh.files = self.files.iter().flatten().count();
+        Ok(refresh)
+    }
+
+    // the files that might contain at least one of the patterns, relative to the indexed directory
+    pub fn candidates<S: AsRef<str>>(&self, patterns: &[S]) -> Vec<String> {
+        let mut ids: BTreeSet<u32> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.as_ref().is_some_and(|f| f.opaque))
+            .map(|(id, _)| id as u32)
+            .collect();
+
+        for p in patterns {
+            let wanted = trigrams(&fold::fold_str(p.as_ref()));
+            if wanted.is_empty() {
+                return self
+                    .files
+                    .iter()
+                    .flatten()
+                    .map(|f| f.path.clone())
+                    .collect();
+            }
+            // the intersection of the lists of all the trigrams of the pattern
+            let mut found: Option<BTreeSet<u32>> = None;
+            for t in &wanted {
+                let list: BTreeSet<u32> = match self.postings.get(t) {
+                    Some(ids) => ids.iter().copied().collect(),
+                    None => BTreeSet::new(),
+                };
+                found = Some(match found {
+                    Some(f) => f.intersection(&list).copied().collect(),
+                    None => list,
+                });
+            }
+            ids.extend(found.unwrap_or_default());
+        }
+
+        ids.into_iter()
+            .filter_map(|id| self.files[id as usize].as_ref())
+            .map(|f| f.path.clone())
+            .collect()
+    }
+
+    // drops the removed files, giving new ids to the others
+    fn compact(&mut self) {
+        let mut new_ids = vec![None; self.files.len()];
+        let mut files = vec![];
+        for (id, f) in self.files.drain(..).enumerate() {
+            if let Some(f) = f {
+                new_ids[id] = Some(files.len() as u32);
+                files.push(Some(f));
+            }
+        }
+        for ids in self.postings.values_mut() {
+            *ids = ids.iter().filter_map(|&id| new_ids[id as usize]).collect();
+        }
+        self.files = files;
+    }
+
+    // the ids in the postings are the positions in "files": the index must have been compacted
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        let files: Vec<&FileEntry> = self.files.iter().flatten().collect();
+        out.extend((files.len() as u32).to_le_bytes());
+        for f in files {
+            out.extend((f.path.len() as u16).to_le_bytes());
+            out.extend(f.path.as_bytes());
+            out.extend(f.mtime.0.to_le_bytes());
+            out.extend(f.mtime.1.to_le_bytes());
+            out.extend(f.size.to_le_bytes());
+            out.push(f.opaque as u8);
+        }
+        out.extend((self.postings.len() as u32).to_le_bytes());
+        for (t, ids) in &self.postings {
+            out.extend(t.to_le_bytes());
+            out.extend((ids.len() as u32).to_le_bytes());
+            for id in ids {
+                out.extend(id.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Index, String> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a minigrep index"));
+        }
+        let version = r.take(1)?[0];
+        if version != VERSION {
+            return Err(format!(
+                "index version {} is not supported (want {}): run \"minigrep index\" again",
+                version, VERSION
+            ));
+        }
+
+        let mut index = Index::default();
+        for _ in 0..r.u32()? {
+            let len = u16::from_le_bytes(r.array()?) as usize;
+            let path = String::from_utf8(r.take(len)?.to_vec()).map_err(|_| "invalid path")?;
+            let mtime = (r.u64()?, r.u32()?);
+            let size = r.u64()?;
+            let opaque = r.take(1)?[0] != 0;
+            index.files.push(Some(FileEntry {
+                path,
+                mtime,
+                size,
+                opaque,
+            }));
+        }
+        for _ in 0..r.u32()? {
+            let t = r.u32()?;
+            let n = r.u32()?;
+            let mut ids = Vec::with_capacity(n as usize);
+            for _ in 0..n {
+                let id = r.u32()?;
+                if id as usize >= index.files.len() {
+                    return Err(String::from("corrupted index"));
+                }
+                ids.push(id);
+            }
+            index.postings.insert(t, ids);
+        }
+        Ok(index)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let s = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or("truncated index")?;
+        self.pos += n;
+        Ok(s)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut a = [0; N];
+        a.copy_from_slice(self.take(N)?);
+        Ok(a)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+// the distinct trigrams of the text, packed in a u32; the ones spanning a line break are useless for us
+pub fn trigrams(text: &str) -> BTreeSet<u32> {
+    text.as_bytes()
+        .windows(3)
+        .filter(|w| !w.contains(&b'\n'))
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], 0]))
+        .collect()
+}
+
+// every file under dir, skipping the hidden ones (the index itself included), with its mtime and size
+fn walk(dir: &Path, rel: &Path, out: &mut HashMap<String, Stamp>) -> Result<(), String> {
+    let full = dir.join(rel);
+    let entries = fs::read_dir(&full).map_err(|e| format!("{}: {}", full.display(), e))?;
+    for e in entries {
+        let e = e.map_err(|e| format!("{}: {}", full.display(), e))?;
+        let name = e.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        let rel = rel.join(&name);
+        let meta = e
+            .metadata()
+            .map_err(|e| format!("{}: {}", rel.display(), e))?;
+        if meta.is_dir() {
+            walk(dir, &rel, out)?;
+        } else if meta.is_file() {
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()));
+            out.insert(rel.to_string_lossy().to_string(), (mtime, meta.len()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn narrows_and_refreshes() {
+        let dir = std::env::temp_dir().join(format!("minigrep-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.log"), "DCT BRYTO\n").unwrap();
+        fs::write(dir.join("sub/b.log"), "engine fire\n").unwrap();
+        let d = dir.to_str().unwrap();
+
+        let (index, refresh) = update(d).unwrap();
+        assert_eq!((2, 2, 0), (refresh.files, refresh.updated, refresh.removed));
+        assert_eq!(vec!["a.log"], index.candidates(&["bryto"]));
+        assert_eq!(vec!["sub/b.log"], index.candidates(&["FIRE", "nothing"]));
+        // too short to use the index
+        assert_eq!(2, index.candidates(&["DC"]).len());
+
+        // saved and loaded back as it was
+        let loaded = Index::from_bytes(&fs::read(index_path(d)).unwrap()).unwrap();
+        assert_eq!(index, loaded);
+
+        fs::remove_file(dir.join("a.log")).unwrap();
+        fs::write(dir.join("sub/b.log"), "BRYTO again!\n").unwrap();
+        let (index, refresh) = update(d).unwrap();
+        assert_eq!((1, 1, 1), (refresh.files, refresh.updated, refresh.removed));
+        assert_eq!(vec!["sub/b.log"], index.candidates(&["bryto"]));
+        assert!(index.candidates(&["fire"]).is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn newer_format() {
+        let mut bytes = Index::default().to_bytes();
+        bytes[MAGIC.len()] = VERSION + 1;
+        assert!(Index::from_bytes(&bytes)
+            .unwrap_err()
+            .contains("not supported"));
+    }
+}
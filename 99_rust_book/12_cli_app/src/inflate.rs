@@ -0,0 +1,314 @@
+/*
+ * A small INFLATE decoder (RFC 1951), the decompression half of DEFLATE, which is what gzip uses.
+ * It follows the structure of "puff.c" from zlib: simple and readable rather than fast.
+ *
+ * The compressed data is a sequence of blocks, each one:
+ * - stored: the bytes are copied as they are
+ * - compressed with fixed Huffman codes, defined by the RFC
+ * - compressed with dynamic Huffman codes, described at the start of the block (themselves Huffman coded)
+ *
+ * Compressed blocks contain literals (a byte to output) and <length, distance> pairs meaning
+ * "copy length bytes starting distance bytes back in what has been output so far".
+ */
+
+const MAX_BITS: usize = 15;
+
+// bits are read starting from the least significant bit of every byte
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn need(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let b = *self
+                .data
+                .get(self.pos)
+                .ok_or("unexpected end of compressed data")?;
+            self.pos += 1;
+            self.buf |= (b as u32) << self.count;
+            self.count += 8;
+        }
+        let v = self.buf & ((1u32 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Ok(v)
+    }
+
+    // stored blocks start at a byte boundary: the remaining bits of the current byte are dropped
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+// a canonical Huffman code: only the number of codes of every length is needed to rebuild it
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    // the symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        // check that the lengths don't describe more codes than possible
+        let mut left: i32 = 1;
+        for &c in &counts[1..] {
+            left = left * 2 - c as i32;
+            if left < 0 {
+                return Err(String::from("invalid Huffman code lengths"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    // reads one bit at a time until the code read so far is one of the codes of that length
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= bits.need(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(String::from("invalid Huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// the order in which the lengths of the code lengths code are stored
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/*
+ * Decompresses raw DEFLATE data, returning the output and the number of input bytes used:
+ * in a gzip file, the trailer comes right after the compressed data.
+ */
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        buf: 0,
+        count: 0,
+    };
+    let mut out = vec![];
+    loop {
+        let last = bits.need(1)? == 1;
+        match bits.need(2)? {
+            0 => stored(&mut bits, &mut out)?,
+            1 => {
+                let (lit, dist) = fixed()?;
+                codes(&mut bits, &mut out, &lit, &dist)?
+            }
+            2 => {
+                let (lit, dist) = dynamic(&mut bits)?;
+                codes(&mut bits, &mut out, &lit, &dist)?
+            }
+            _ => return Err(String::from("invalid block type")),
+        }
+        if last {
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>) -> Result<(), String> {
+    bits.align();
+    let d = bits.data;
+    let p = bits.pos;
+    if p + 4 > d.len() {
+        return Err(String::from("unexpected end of compressed data"));
+    }
+    let len = u16::from_le_bytes([d[p], d[p + 1]]);
+    let nlen = u16::from_le_bytes([d[p + 2], d[p + 3]]);
+    if len != !nlen {
+        return Err(String::from("corrupted stored block length"));
+    }
+    let start = p + 4;
+    let end = start + len as usize;
+    let block = d
+        .get(start..end)
+        .ok_or("unexpected end of compressed data")?;
+    out.extend_from_slice(block);
+    bits.pos = end;
+    Ok(())
+}
+
+fn fixed() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    for (i, l) in lengths.iter_mut().enumerate() {
+        *l = match i {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let nlen = bits.need(5)? as usize + 257;
+    let ndist = bits.need(5)? as usize + 1;
+    let ncode = bits.need(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(String::from("too many length or distance codes"));
+    }
+
+    let mut clens = [0u8; 19];
+    for &i in &CLEN_ORDER[..ncode] {
+        clens[i] = bits.need(3)? as u8;
+    }
+    let clen = Huffman::new(&clens)?;
+
+    // the lengths of both codes, one after the other, with run-length encoding
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < nlen + ndist {
+        let symbol = clen.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i]
+                    .last()
+                    .ok_or("repeat with no previous length")?;
+                (previous, 3 + bits.need(2)? as usize)
+            }
+            17 => (0, 3 + bits.need(3)? as usize),
+            _ => (0, 11 + bits.need(7)? as usize),
+        };
+        if i + repeat > nlen + ndist {
+            return Err(String::from("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(String::from("missing end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, lit: &Huffman, dist: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = lit.decode(bits)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(()); // end of block
+        }
+
+        let s = symbol - 257;
+        if s >= LENGTH_BASE.len() {
+            return Err(String::from("invalid length code"));
+        }
+        let len = LENGTH_BASE[s] as usize + bits.need(LENGTH_EXTRA[s] as u32)? as usize;
+        let d = dist.decode(bits)? as usize;
+        if d >= DIST_BASE.len() {
+            return Err(String::from("invalid distance code"));
+        }
+        let distance = DIST_BASE[d] as usize + bits.need(DIST_EXTRA[d] as u32)? as usize;
+        if distance > out.len() {
+            return Err(String::from("distance too far back"));
+        }
+        // byte by byte, as the bytes to copy can overlap with the ones being written (e.g. "aaaa...")
+        let from = out.len() - distance;
+        for k in 0..len {
+            out.push(out[from + k]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // produced with Python's zlib.compressobj(level, zlib.DEFLATED, -15)
+    #[test]
+    fn stored_block() {
+        let data = [1, 5, 0, 250, 255, 104, 101, 108, 108, 111];
+        assert_eq!((b"hello".to_vec(), 10), inflate(&data).unwrap());
+    }
+
+    #[test]
+    fn fixed_block() {
+        let data = [
+            115, 113, 14, 81, 112, 10, 242, 12, 241, 87, 112, 246, 113, 117, 12, 114, 117, 81, 112,
+            12, 86, 48, 52, 50, 54, 1, 0,
+        ];
+        assert_eq!(
+            b"DCT BRITO CLEARED AS 1234".to_vec(),
+            inflate(&data).unwrap().0
+        );
+    }
+
+    #[test]
+    fn dynamic_block() {
+        let mut expected = vec![];
+        for i in 0..30 {
+            expected.extend_from_slice(format!("line {} tell {}\n", i, i * 7 % 13).as_bytes());
+        }
+        let (out, _) = inflate(&DYNAMIC).unwrap();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn corrupted() {
+        assert!(inflate(&[7]).is_err());
+        assert!(inflate(&[1, 5, 0, 0, 0]).is_err());
+    }
+
+    const DYNAMIC: [u8; 123] = [
+        85, 209, 59, 10, 195, 64, 20, 67, 209, 62, 171, 152, 37, 88, 154, 255, 130, 92, 4, 6, 87,
+        217, 63, 33, 72, 41, 84, 30, 6, 252, 116, 241, 121, 63, 119, 185, 202, 231, 62, 167, 92,
+        175, 243, 19, 164, 41, 81, 130, 84, 165, 37, 53, 137, 82, 151, 182, 52, 164, 42, 77, 127,
+        197, 39, 150, 216, 164, 237, 71, 223, 128, 231, 116, 211, 123, 224, 51, 240, 162, 97, 214,
+        28, 223, 98, 61, 122, 204, 199, 136, 253, 152, 17, 128, 21, 5, 216, 145, 192, 43, 27, 136,
+        136, 32, 179, 130, 53, 42, 216, 178, 130, 61, 42, 56, 162, 130, 51, 255, 193, 138, 10, 238,
+        127, 197, 23,
+    ];
+}
@@ -0,0 +1,628 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use crate::{
+    aho_corasick::AhoCorasick, decode, line_spans, non_overlapping, normalize, search_patterns,
+    terminal, CaseMode, Encoding, Normalization,
+};
+
+/*
+ * "minigrep --interactive FILE..." is a small full-screen UI:
+ *   > bryto                                        12 matches
+ *   sim.log:12: DCT BRYTO CLEARED FL350
+ *   ...
+ *   ── sim.log ─────────────────────────────────────────────
+ *       11 ALT 350
+ *   >   12 DCT BRYTO CLEARED FL350
+ * The results are updated as the query is typed, the arrows (or Ctrl-P/Ctrl-N) move through them and
+ * the bottom pane shows the lines around the selected one. Enter quits and prints the selected line,
+ * Esc or Ctrl-C quits without printing anything.
+ *
+ * The files are read once, then a background thread does the searching, so typing never waits for it:
+ * - every query typed gets a new "generation" number, and the results are tagged with it
+ * - the thread checks the latest generation between chunks of lines, and drops a search as soon as the
+ *   query has changed, instead of finishing a search nobody is waiting for anymore
+ * - the results of a search are sent chunk by chunk, and shown while the rest is still being searched
+ */
+
+// the lines are searched by chunks of about this many bytes
+const CHUNK: usize = 1 << 20;
+// more results than this aren't useful to browse: the search stops there
+const MAX_HITS: usize = 10_000;
+
+// a file as it has been read, with the offset of every line
+struct Doc {
+    path: String,
+    content: String,
+    lines: Vec<usize>,
+}
+
+impl Doc {
+    fn new(path: String, content: String) -> Doc {
+        let lines = line_spans(&content).map(|(offset, _)| offset).collect();
+        Doc {
+            path,
+            content,
+            lines,
+        }
+    }
+
+    // the line at index i (from 0), without its '\n'
+    fn line(&self, i: usize) -> &str {
+        let end = self.lines.get(i + 1).copied().unwrap_or(self.content.len());
+        let l = &self.content[self.lines[i]..end];
+        let l = l.strip_suffix('\n').unwrap_or(l);
+        l.strip_suffix('\r').unwrap_or(l)
+    }
+}
+
+// a matching line
+#[derive(Debug, Clone, PartialEq)]
+struct Hit {
+    doc: usize,
+    // from 1
+    line_number: usize,
+    // byte ranges of the matches in the line
+    matches: Vec<(usize, usize)>,
+}
+
+struct Request {
+    generation: usize,
+    query: String,
+}
+
+enum Update {
+    Hits { generation: usize, hits: Vec<Hit> },
+    // "truncated" when the search stopped at MAX_HITS
+    Done { generation: usize, truncated: bool },
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Backspace,
+    ClearQuery,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    Quit,
+}
+
+pub fn run(
+    files: Vec<String>,
+    case: CaseMode,
+    encoding: Encoding,
+    form: Option<Normalization>,
+    text: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("--interactive needs a terminal".into());
+    }
+
+    let mut docs = vec![];
+    let mut skipped = vec![];
+    for path in files {
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let decoded = decode::decode(&bytes, encoding);
+                if decoded.binary_offset.is_some() && !text {
+                    skipped.push(format!("{}: binary file, skipped", path));
+                    continue;
+                }
+                let content = match form {
+                    Some(form) => normalize(&decoded.text, form),
+                    None => decoded.text,
+                };
+                docs.push(Doc::new(path, content));
+            }
+            Err(e) => skipped.push(format!("{}: {}", path, e)),
+        }
+    }
+    let docs = Arc::new(docs);
+
+    let latest = Arc::new(AtomicUsize::new(0));
+    let (requests, queries) = mpsc::channel::<Request>();
+    let (updates, results) = mpsc::channel::<Update>();
+    {
+        let docs = Arc::clone(&docs);
+        let latest = Arc::clone(&latest);
+        thread::spawn(move || {
+            while let Ok(mut req) = queries.recv() {
+                // only the last query typed matters
+                while let Ok(newer) = queries.try_recv() {
+                    req = newer;
+                }
+                search(&docs, &req.query, case, req.generation, &latest, &updates);
+            }
+        });
+    }
+
+    let raw = terminal::RawMode::enable()?;
+    let mut out = io::stdout();
+    // the alternate screen: what was on the terminal is back when the UI quits
+    out.write_all(b"\x1b[?1049h")?;
+    let chosen = event_loop(&docs, &latest, &requests, &results);
+    out.write_all(b"\x1b[?1049l")?;
+    out.flush()?;
+    drop(raw);
+
+    for s in skipped {
+        eprintln!("minigrep: {}", s);
+    }
+    if let Some(hit) = chosen? {
+        let doc = &docs[hit.doc];
+        println!(
+            "{}:{}:{}",
+            doc.path,
+            hit.line_number,
+            doc.line(hit.line_number - 1)
+        );
+    }
+    Ok(())
+}
+
+// returns the hit chosen with Enter
+fn event_loop(
+    docs: &[Doc],
+    latest: &AtomicUsize,
+    requests: &mpsc::Sender<Request>,
+    results: &mpsc::Receiver<Update>,
+) -> io::Result<Option<Hit>> {
+    let mut ui = Ui::default();
+    let mut stdin = io::stdin();
+    let mut out = io::stdout();
+    let mut buf = [0; 64];
+    let mut size = (0, 0);
+    let mut dirty = true;
+    loop {
+        let s = terminal::size()?;
+        if s != size {
+            size = s;
+            dirty = true;
+        }
+        let (list_height, _) = layout(size.0);
+
+        // returns after a tenth of a second at most, see terminal.rs
+        let n = stdin.read(&mut buf)?;
+        let mut changed = false;
+        for key in keys(&buf[..n]) {
+            match key {
+                Key::Quit => return Ok(None),
+                Key::Enter if !ui.hits.is_empty() => return Ok(Some(ui.hits[ui.selected].clone())),
+                Key::Enter => {}
+                Key::Up => ui.select(-1, list_height),
+                Key::Down => ui.select(1, list_height),
+                Key::PageUp => ui.select(-(list_height as isize), list_height),
+                Key::PageDown => ui.select(list_height as isize, list_height),
+                Key::Char(c) => {
+                    ui.query.push(c);
+                    changed = true;
+                }
+                Key::Backspace => changed |= ui.query.pop().is_some(),
+                Key::ClearQuery => {
+                    changed |= !ui.query.is_empty();
+                    ui.query.clear();
+                }
+            }
+            dirty = true;
+        }
+
+        if changed {
+            ui.restart();
+            latest.store(ui.generation, Ordering::Relaxed);
+            requests
+                .send(Request {
+                    generation: ui.generation,
+                    query: ui.query.clone(),
+                })
+                .map_err(|_| io::Error::other("the search thread has stopped"))?;
+        }
+        while let Ok(update) = results.try_recv() {
+            match update {
+                Update::Hits { generation, hits } if generation == ui.generation => {
+                    ui.hits.extend(hits);
+                    dirty = true;
+                }
+                Update::Done {
+                    generation,
+                    truncated,
+                } if generation == ui.generation => {
+                    ui.searching = false;
+                    ui.truncated = truncated;
+                    dirty = true;
+                }
+                // the results of a query that has been changed since
+                _ => {}
+            }
+        }
+
+        if dirty {
+            out.write_all(ui.draw(docs, size.0, size.1).as_bytes())?;
+            out.flush()?;
+            dirty = false;
+        }
+    }
+}
+
+// runs on the background thread, see the top of the file
+fn search(
+    docs: &[Doc],
+    query: &str,
+    case: CaseMode,
+    generation: usize,
+    latest: &AtomicUsize,
+    updates: &mpsc::Sender<Update>,
+) {
+    let mut found = 0;
+    let mut truncated = false;
+    // an empty query would match every line
+    if !query.is_empty() {
+        let ac = AhoCorasick::new(&[query], !case.is_sensitive(&[query]));
+        'docs: for (d, doc) in docs.iter().enumerate() {
+            let mut first = 0;
+            while first < doc.lines.len() {
+                if latest.load(Ordering::Relaxed) != generation {
+                    return;
+                }
+                // the lines from "first" to "last" (excluded): at least one, even a very long one
+                let from = doc.lines[first];
+                let last = doc
+                    .lines
+                    .partition_point(|&o| o < from + CHUNK)
+                    .max(first + 1);
+                let to = doc.lines.get(last).copied().unwrap_or(doc.content.len());
+                let mut hits: Vec<Hit> = search_patterns(&ac, &doc.content[from..to])
+                    .into_iter()
+                    .map(|m| Hit {
+                        doc: d,
+                        line_number: first + m.line_number,
+                        matches: non_overlapping(&m.matches)
+                            .iter()
+                            .map(|h| (h.start, h.end))
+                            .collect(),
+                    })
+                    .collect();
+                if found + hits.len() >= MAX_HITS {
+                    hits.truncate(MAX_HITS - found);
+                    truncated = true;
+                }
+                found += hits.len();
+                // the UI has quit
+                if updates.send(Update::Hits { generation, hits }).is_err() {
+                    return;
+                }
+                if truncated {
+                    break 'docs;
+                }
+                first = last;
+            }
+        }
+    }
+    let _ = updates.send(Update::Done {
+        generation,
+        truncated,
+    });
+}
+
+// the keys in what a read returned: the special ones are escape sequences
+fn keys(bytes: &[u8]) -> Vec<Key> {
+    let mut res = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        match b {
+            // Ctrl-C, and Esc when nothing follows it
+            0x03 => res.push(Key::Quit),
+            0x1b if i == bytes.len() => res.push(Key::Quit),
+            0x1b => {
+                // "\x1b[" or "\x1bO", parameters, then a final byte between '@' and '~'.
+                // Anything else is Alt with a key, ignored too
+                let start = i;
+                i += 1;
+                if matches!(bytes[start], b'[' | b'O') {
+                    while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                match &bytes[start..i.min(bytes.len())] {
+                    b"[A" | b"OA" => res.push(Key::Up),
+                    b"[B" | b"OB" => res.push(Key::Down),
+                    b"[5~" => res.push(Key::PageUp),
+                    b"[6~" => res.push(Key::PageDown),
+                    _ => {}
+                }
+            }
+            b'\r' | b'\n' => res.push(Key::Enter),
+            0x7f | 0x08 => res.push(Key::Backspace),
+            0x15 => res.push(Key::ClearQuery),
+            0x10 => res.push(Key::Up),
+            0x0e => res.push(Key::Down),
+            b if b < 0x20 => {}
+            b => {
+                // the length of a UTF-8 char is given by its first byte
+                let len = match b {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => 1,
+                };
+                let end = (i - 1 + len).min(bytes.len());
+                if let Ok(s) = std::str::from_utf8(&bytes[i - 1..end]) {
+                    res.extend(s.chars().map(Key::Char));
+                }
+                i = end;
+            }
+        }
+    }
+    res
+}
+
+// the heights of the list of results and of the preview, below the query
+fn layout(rows: usize) -> (usize, usize) {
+    // one row for the query and one above the preview
+    let body = rows.saturating_sub(2);
+    let preview = body * 2 / 5;
+    ((body - preview).max(1), preview)
+}
+
+#[derive(Default)]
+struct Ui {
+    query: String,
+    generation: usize,
+    hits: Vec<Hit>,
+    searching: bool,
+    truncated: bool,
+    selected: usize,
+    // the first hit shown in the list
+    top: usize,
+}
+
+impl Ui {
+    // for a new query
+    fn restart(&mut self) {
+        self.generation += 1;
+        self.hits.clear();
+        self.searching = !self.query.is_empty();
+        self.truncated = false;
+        self.selected = 0;
+        self.top = 0;
+    }
+
+    // moves the selection, and scrolls the list so that it stays visible
+    fn select(&mut self, delta: isize, height: usize) {
+        if self.hits.is_empty() {
+            return;
+        }
+        let last = self.hits.len() as isize - 1;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + height {
+            self.top = self.selected + 1 - height;
+        }
+    }
+
+    // the whole screen, every row is written over
+    fn draw(&self, docs: &[Doc], rows: usize, cols: usize) -> String {
+        let (list_height, preview_height) = layout(rows);
+        // no cursor flickering around while drawing
+        let mut out = String::from("\x1b[?25l");
+
+        let status = match (self.query.is_empty(), self.truncated) {
+            (true, _) => String::from("type to search"),
+            (false, true) => format!("{}+ matches", self.hits.len()),
+            (false, false) => format!("{} matches", self.hits.len()),
+        };
+        let status = if self.searching {
+            status + ", searching..."
+        } else {
+            status
+        };
+        out.push_str("\x1b[1;1H\x1b[K> ");
+        let room = cols.saturating_sub(status.len() + 3);
+        out.push_str(&styled(&self.query, &[], room, ""));
+        if status.len() + 2 < cols {
+            out.push_str(&format!(
+                "\x1b[1;{}H\x1b[2m{}\x1b[0m",
+                cols - status.len() + 1,
+                status
+            ));
+        }
+
+        for row in 0..list_height {
+            out.push_str(&format!("\x1b[{};1H\x1b[K", row + 2));
+            let Some(hit) = self.hits.get(self.top + row) else {
+                continue;
+            };
+            let doc = &docs[hit.doc];
+            let prefix = format!("{}:{}: ", doc.path, hit.line_number);
+            let base = if self.top + row == self.selected {
+                "\x1b[7m"
+            } else {
+                ""
+            };
+            out.push_str(base);
+            out.push_str(&styled(&prefix, &[], cols, base));
+            let line = doc.line(hit.line_number - 1);
+            let room = cols.saturating_sub(prefix.chars().count());
+            out.push_str(&styled(line, &hit.matches, room, base));
+            // or the next "\x1b[K" would clear the row below in reverse video
+            out.push_str("\x1b[0m");
+        }
+
+        let separator_row = list_height + 2;
+        out.push_str(&format!("\x1b[{};1H\x1b[K", separator_row));
+        let selected = self.hits.get(self.selected);
+        let title = selected.map_or(String::new(), |h| format!(" {} ", docs[h.doc].path));
+        let title = styled(&title, &[], cols.saturating_sub(2), "");
+        out.push_str(&format!("\x1b[2m──{}", title));
+        let used = 2 + title.chars().count().min(cols);
+        out.push_str(&"─".repeat(cols.saturating_sub(used)));
+        out.push_str("\x1b[0m");
+
+        // the selected line in the middle of the preview, when there are enough lines around it
+        for row in 0..preview_height {
+            out.push_str(&format!("\x1b[{};1H\x1b[K", separator_row + 1 + row));
+            let Some(hit) = selected else {
+                continue;
+            };
+            let doc = &docs[hit.doc];
+            let first = (hit.line_number - 1)
+                .saturating_sub(preview_height / 2)
+                .min(doc.lines.len().saturating_sub(preview_height));
+            let i = first + row;
+            if i >= doc.lines.len() {
+                continue;
+            }
+            let (mark, matches) = if i + 1 == hit.line_number {
+                ('>', hit.matches.as_slice())
+            } else {
+                (' ', &[][..])
+            };
+            let gutter = format!("{} {:>6} ", mark, i + 1);
+            out.push_str(&format!("\x1b[2m{}\x1b[0m", gutter));
+            let room = cols.saturating_sub(gutter.len());
+            out.push_str(&styled(doc.line(i), matches, room, ""));
+        }
+
+        let cursor = 3 + self.query.chars().count().min(room);
+        out.push_str(&format!("\x1b[1;{}H\x1b[?25h", cursor));
+        out
+    }
+}
+
+/*
+ * "text" cut to "width" chars, with the matches in bold red, on top of the "base" style of the row.
+ * Tabs and other control chars become spaces, so that they can't move the cursor around. When the
+ * first match is past the right edge, the start of the text is skipped to bring it into view.
+ * Every char is counted as one column: the wide ones (e.g. CJK) will make the row overflow a bit.
+ */
+fn styled(text: &str, matches: &[(usize, usize)], width: usize, base: &str) -> String {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut skip = 0;
+    if let Some(&(start, end)) = matches.first() {
+        let end_char = chars.partition_point(|&(i, _)| i < end);
+        if end_char > width {
+            skip = chars
+                .partition_point(|&(i, _)| i < start)
+                .saturating_sub(width / 3);
+        }
+    }
+
+    let mut out = String::new();
+    for &(i, c) in chars.iter().skip(skip).take(width) {
+        if matches.iter().any(|&(start, _)| start == i) {
+            out.push_str("\x1b[1;31m");
+        }
+        if matches.iter().any(|&(_, end)| end == i) {
+            out.push_str("\x1b[0m");
+            out.push_str(base);
+        }
+        out.push(if c.is_control() { ' ' } else { c });
+    }
+    if !matches.is_empty() || !base.is_empty() {
+        out.push_str("\x1b[0m");
+        out.push_str(base);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_sequences() {
+        assert_eq!(
+            vec![
+                Key::Char('b'),
+                Key::Char('é'),
+                Key::Up,
+                Key::Down,
+                Key::PageDown,
+                Key::Backspace,
+                Key::Enter
+            ],
+            keys("bé\x1b[A\x1bOB\x1b[6~\x7f\r".as_bytes())
+        );
+        // unknown sequences are skipped, Esc alone quits
+        assert_eq!(vec![Key::Char('x'), Key::Quit], keys(b"\x1b[1;5Cx\x1b"));
+    }
+
+    #[test]
+    fn selection_scrolls_the_list() {
+        let mut ui = Ui {
+            hits: (1..=10)
+                .map(|n| Hit {
+                    doc: 0,
+                    line_number: n,
+                    matches: vec![],
+                })
+                .collect(),
+            ..Ui::default()
+        };
+        ui.select(4, 3);
+        assert_eq!((4, 2), (ui.selected, ui.top));
+        ui.select(100, 3);
+        assert_eq!((9, 7), (ui.selected, ui.top));
+        ui.select(-8, 3);
+        assert_eq!((1, 1), (ui.selected, ui.top));
+    }
+
+    #[test]
+    fn styled_lines() {
+        assert_eq!("a b", styled("a\tb", &[], 10, ""));
+        assert_eq!("ab", styled("abc", &[], 2, ""));
+        assert_eq!(
+            "DCT \x1b[1;31mBRYTO\x1b[0m FL\x1b[0m",
+            styled("DCT BRYTO FL350", &[(4, 9)], 12, "")
+        );
+        // the match is brought into view
+        let long = format!("{}BRYTO", "x".repeat(20));
+        assert_eq!(
+            "xxx\x1b[1;31mBRYTO\x1b[0m",
+            styled(&long, &[(20, 25)], 9, "")
+        );
+    }
+
+    #[test]
+    fn background_search() {
+        // the second match is in the second chunk
+        let content = format!("BRYTO\n{}\nnothing\nbryto twice bryto\n", "x".repeat(CHUNK));
+        let docs = vec![Doc::new(String::from("sim.log"), content)];
+        let latest = AtomicUsize::new(7);
+        let (tx, rx) = mpsc::channel();
+        search(&docs, "bryto", CaseMode::Insensitive, 7, &latest, &tx);
+
+        let mut hits = vec![];
+        for update in rx.try_iter() {
+            match update {
+                Update::Hits { hits: h, .. } => hits.extend(h),
+                Update::Done { truncated, .. } => assert!(!truncated),
+            }
+        }
+        let found: Vec<(usize, usize)> = hits
+            .iter()
+            .map(|h| (h.line_number, h.matches.len()))
+            .collect();
+        assert_eq!(vec![(1, 1), (4, 2)], found);
+        assert_eq!("bryto twice bryto", docs[0].line(3));
+
+        // a newer query: this one is dropped
+        let (tx, rx) = mpsc::channel();
+        search(&docs, "bryto", CaseMode::Insensitive, 6, &latest, &tx);
+        assert!(rx.try_iter().next().is_none());
+    }
+}
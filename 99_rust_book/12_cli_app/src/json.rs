@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use crate::{aho_corasick::Match, Stats};
+
+/*
+ * The "--json" output: one JSON object per line (JSON Lines), with the same shape ripgrep uses:
+ *
+ * {"type":"begin","data":{"path":{"text":"poem.txt"}}}
+ * {"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"..."},"line_number":3,"absolute_offset":61,"submatches":[...]}}
+ * {"type":"context","data":{...same as match, with no submatches...}}
+ * {"type":"end","data":{"path":{"text":"poem.txt"},"binary_offset":null,"stats":{...}}}
+ * {"type":"summary","data":{"elapsed_total":{...},"stats":{...}}}
+ *
+ * "begin" and "end" are only printed for the files with at least one match, "summary" once at the very end.
+ * There are only a handful of object shapes, so they are written by hand instead of pulling in serde.
+ */
+
+// a JSON string literal, quotes included
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // the other control chars have no short escape
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn text(s: &str) -> String {
+    format!("{{\"text\":{}}}", string(s))
+}
+
+fn duration(d: Duration) -> String {
+    format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        d.as_secs(),
+        d.subsec_nanos(),
+        d.as_secs_f64()
+    )
+}
+
+fn stats(s: &Stats) -> String {
+    format!(
+        "{{\"elapsed\":{},\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"matched_lines\":{},\"matches\":{}}}",
+        duration(s.elapsed),
+        s.searches,
+        s.searches_with_match,
+        s.bytes_searched,
+        s.matched_lines,
+        s.matches
+    )
+}
+
+pub fn begin(path: &str) -> String {
+    format!(
+        "{{\"type\":\"begin\",\"data\":{{\"path\":{}}}}}\n",
+        text(path)
+    )
+}
+
+/*
+ * "matches" are the spans found in "line", with offsets relative to the start of the line.
+ * With "fuzzy", every submatch also has the "distance" ripgrep doesn't know about.
+ */
+pub fn line(
+    kind: &str,
+    path: &str,
+    line: &str,
+    line_number: usize,
+    offset: usize,
+    matches: &[Match],
+    fuzzy: bool,
+) -> String {
+    let submatches: Vec<String> = matches
+        .iter()
+        .map(|m| {
+            let distance = if fuzzy {
+                format!(",\"distance\":{}", m.distance)
+            } else {
+                String::new()
+            };
+            format!(
+                "{{\"match\":{},\"start\":{},\"end\":{}{}}}",
+                text(&line[m.start..m.end]),
+                m.start,
+                m.end,
+                distance
+            )
+        })
+        .collect();
+    format!(
+        "{{\"type\":{},\"data\":{{\"path\":{},\"lines\":{},\"line_number\":{},\"absolute_offset\":{},\"submatches\":[{}]}}}}\n",
+        string(kind),
+        text(path),
+        text(line),
+        line_number,
+        offset,
+        submatches.join(",")
+    )
+}
+
+// "binary_offset" is where the NUL byte of a binary file has been found
+pub fn end(path: &str, binary_offset: Option<usize>, s: &Stats) -> String {
+    let binary_offset = match binary_offset {
+        Some(at) => at.to_string(),
+        None => String::from("null"),
+    };
+    format!(
+        "{{\"type\":\"end\",\"data\":{{\"path\":{},\"binary_offset\":{},\"stats\":{}}}}}\n",
+        text(path),
+        binary_offset,
+        stats(s)
+    )
+}
+
+pub fn summary(elapsed_total: Duration, s: &Stats) -> String {
+    format!(
+        "{{\"type\":\"summary\",\"data\":{{\"elapsed_total\":{},\"stats\":{}}}}}\n",
+        duration(elapsed_total),
+        stats(s)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(
+            r#""say \"hi\"\n\\o/\u0007""#,
+            string("say \"hi\"\n\\o/\u{7}")
+        );
+    }
+
+    #[test]
+    fn match_event() {
+        let m = Match {
+            pattern: 0,
+            start: 4,
+            end: 6,
+            distance: 0,
+        };
+        assert_eq!(
+            "{\"type\":\"match\",\"data\":{\"path\":{\"text\":\"a.log\"},\"lines\":{\"text\":\"hey me\"},\"line_number\":2,\"absolute_offset\":10,\"submatches\":[{\"match\":{\"text\":\"me\"},\"start\":4,\"end\":6}]}}\n",
+            line("match", "a.log", "hey me", 2, 10, &[m], false)
+        );
+    }
+}
@@ -1,58 +1,901 @@
-use std::{error::Error, fs, env};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    io::{self, Write},
+    ops::AddAssign,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+pub mod aho_corasick;
+pub mod archive;
+mod args;
+pub mod csv;
+pub mod decode;
+pub mod expr;
+pub mod fold;
+pub mod follow;
+pub mod fuzzy;
+pub mod index;
+pub mod inflate;
+mod interactive;
+mod json;
+pub mod pool;
+mod replace;
+pub mod searcher;
+mod terminal;
+
+use aho_corasick::{AhoCorasick, Match};
+pub use args::{
+    help, parse, version, ArgError, CaseMode, Column, Command, Config, Encoding, Normalization,
+    SortBy, Table,
+};
+use expr::Expr;
+use fuzzy::Fuzzy;
+use searcher::Searcher;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let content = fs::read_to_string(config.filename)?;
+    let started = Instant::now();
+    let mut patterns = config.patterns;
+    for f in &config.pattern_files {
+        // blank lines are skipped: an empty pattern would match every line
+        let list = fs::read_to_string(f)?;
+        patterns.extend(list.lines().filter(|l| !l.is_empty()).map(String::from));
+    }
 
-    // remember: Rust lets you return values from if statements
-    let res = if config.case_sensitive {
-        search_case_sensitive(&config.query, &content)
-    } else {
-        search(&config.query, &content)
+    if config.multiline {
+        patterns = patterns.iter().map(|p| unescape(p)).collect();
+    }
+    if let Some(form) = config.normalize {
+        patterns = patterns.iter().map(|p| normalize(p, form)).collect();
+    }
+
+    let mut files = config.files;
+    if config.index {
+        files = indexed_candidates(&files, &patterns)?;
+    }
+    if config.sort == SortBy::Path {
+        files.sort();
+    }
+    if config.interactive {
+        return interactive::run(
+            files,
+            config.case,
+            config.encoding,
+            config.normalize,
+            config.text,
+        );
+    }
+    let threads = match config.threads {
+        0 => pool::default_threads(),
+        n => n,
+    };
+
+    let case_insensitive = !config.case.is_sensitive(&patterns);
+    let fuzzy = config
+        .fuzzy
+        .map(|k| Fuzzy::new(&patterns, k, case_insensitive))
+        .transpose()?;
+    let searcher = match config.searcher {
+        // there's nothing to fold and no pattern to tell apart: the searchers don't know how to
+        Some(_) if patterns.len() != 1 || patterns[0].is_empty() || case_insensitive => {
+            return Err(
+                "--searcher needs a single pattern, not empty, and a case sensitive search (-s)"
+                    .into(),
+            )
+        }
+        Some(s) => Some(s.build(&patterns[0])),
+        None => None,
     };
 
-    for l in res {
-        println!("{}", l);
+    let search = FileSearch {
+        ac: AhoCorasick::new(&patterns, case_insensitive),
+        fuzzy,
+        searcher,
+        with_filename: files.len() > 1,
+        patterns,
+        line_number: config.line_number,
+        max_count: config.max_count,
+        context: config.context,
+        json: config.json,
+        replace: config.replace,
+        in_place: config.in_place,
+        backup: config.backup,
+        normalize: config.normalize,
+        column: config.column,
+        text: config.text,
+        encoding: config.encoding,
+        search_zip: config.search_zip,
+        expr: config.expr.map(|q| q.expr),
+        paragraph: config.paragraph,
+        multiline: config.multiline,
+        table: config.table,
+    };
+    if config.follow {
+        return follow(&search, files);
+    }
+    let rx = pool::spawn(files.clone(), threads, move |path: String| {
+        search.file(&path)
+    });
+    // a search that panicked failed like one that couldn't read its file: the exit code says so
+    let rx = rx.into_iter().map(move |(i, r)| {
+        let r =
+            r.unwrap_or_else(|panic| Err(format!("{}: the search panicked: {}", files[i], panic)));
+        (i, r)
+    });
+
+    // remember: both arms must have the same type, hence the Box<dyn ..>
+    let results: Box<dyn Iterator<Item = Result<(String, Stats), String>>> = match config.sort {
+        SortBy::Path => Box::new(pool::in_order(rx)),
+        SortBy::None => Box::new(rx.map(|(_, r)| r)),
+    };
+
+    // every file is written in one go, so its lines never get mixed with the ones of another file
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut total = Stats::default();
+    let mut failed = 0;
+    for r in results {
+        match r {
+            Ok((text, stats)) => {
+                out.write_all(text.as_bytes())?;
+                total += stats;
+            }
+            Err(e) => {
+                eprintln!("minigrep: {}", e);
+                failed += 1;
+            }
+        }
+    }
+    if config.json {
+        out.write_all(json::summary(started.elapsed(), &total).as_bytes())?;
     }
 
+    if failed > 0 {
+        return Err(format!("{} file(s) could not be searched", failed).into());
+    }
     Ok(())
 }
 
-pub struct Config {
-    pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool,
+// how often "--follow" looks at the files
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// "--follow" only returns if a file can't be read, or once every file had its "-m" matching lines:
+// it is stopped with Ctrl-C otherwise
+fn follow(search: &FileSearch, files: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut followers = vec![];
+    for f in files {
+        let follower =
+            follow::Follower::new(PathBuf::from(&f), search.encoding, search.line_number)
+                .map_err(|e| format!("{}: {}", f, e))?;
+        // and the number of matching lines it had
+        followers.push((follower, 0));
+    }
+    let stdout = io::stdout();
+    let done = |found: usize| search.max_count.is_some_and(|max| found >= max);
+    loop {
+        for (f, found) in &mut followers {
+            let path = f.path.to_string_lossy().to_string();
+            let poll = f.poll().map_err(|e| format!("{}: {}", path, e))?;
+            if let Some(notice) = poll.notice {
+                eprintln!("minigrep: {}: {}", path, notice);
+            }
+            let mut out = stdout.lock();
+            for (n, line) in &poll.lines {
+                if done(*found) {
+                    break;
+                }
+                if let Some(text) = search.followed(&path, *n, line) {
+                    out.write_all(text.as_bytes())?;
+                    *found += 1;
+                }
+            }
+            out.flush()?;
+        }
+        followers.retain(|(_, found)| !done(*found));
+        if followers.is_empty() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
 }
 
-impl Config {
-    /*
-     * The rust book returns "Result<Config, &str>" and the error returned is "Err("not enought arguments")".
-     *
-     * The return type of the method has been changed to "Result<Config, String>" because I am creating a String
-     * via "format!". Once I do that, I am creating an owned type => "new" now owns that String => if I were to return
-     * the &str from that String, via ".as_str()" for example, I would be extracting a slice from the String => I would
-     * be referencing the value, i.e. borrow.
-     * This cannot be done because when the method exits, the String cease to exist, i.e. dangling reference!!!
-     *
-     * The Rust book can return "Err("not enought arguments")" because the string inside "Err" has been stored in the
-     * program binary, i.e. it is a "&'static str", its lifetime is bound to the program binary.
-     *
-     */
-    pub fn new(args: &[String]) -> Result<Config, String> {
-        if args.len() < 3 {
+// "minigrep index DIR...": only the files changed since the last time are read again
+pub fn run_index(dirs: Vec<String>) -> Result<(), Box<dyn Error>> {
+    for dir in dirs {
+        let (_, r) = index::update(&dir)?;
+        println!(
+            "{}: {} file(s) indexed, {} updated, {} removed",
+            index::index_path(&dir).display(),
+            r.files,
+            r.updated,
+            r.removed
+        );
+    }
+    Ok(())
+}
+
+// the files of the indexed directories that might contain one of the patterns
+fn indexed_candidates(dirs: &[String], patterns: &[String]) -> Result<Vec<String>, String> {
+    let mut files = vec![];
+    for dir in dirs {
+        let (index, _) = index::update(dir)?;
+        for f in index.candidates(patterns) {
+            files.push(Path::new(dir).join(f).to_string_lossy().to_string());
+        }
+    }
+    Ok(files)
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub elapsed: Duration,
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub bytes_searched: usize,
+    pub matched_lines: usize,
+    pub matches: usize,
+}
+
+// lets the totals be summed up with "+="
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.elapsed += other.elapsed;
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+// everything a worker needs to search a file and format what it found
+struct FileSearch {
+    ac: AhoCorasick,
+    // used instead of the automaton with "--fuzzy"
+    fuzzy: Option<Fuzzy>,
+    // and this one with "--searcher"
+    searcher: Option<Box<dyn Searcher>>,
+    patterns: Vec<String>,
+    with_filename: bool,
+    line_number: bool,
+    max_count: Option<usize>,
+    context: usize,
+    json: bool,
+    replace: Option<String>,
+    in_place: bool,
+    backup: bool,
+    normalize: Option<Normalization>,
+    column: bool,
+    text: bool,
+    encoding: Encoding,
+    search_zip: bool,
+    expr: Option<Expr>,
+    paragraph: bool,
+    multiline: bool,
+    table: Option<Table>,
+}
+
+impl FileSearch {
+    fn file(&self, path: &str) -> Result<(String, Stats), String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        if !(self.search_zip && archive::is_archive(path)) {
+            return self.entry(path, &bytes, self.with_filename);
+        }
+
+        if self.in_place {
             return Err(format!(
-                "not enought arguments: found {}, want 3",
-                args.len()
+                "{}: files inside archives can't be rewritten",
+                path
+            ));
+        }
+        let entries = archive::entries(path, bytes).map_err(|e| format!("{}: {}", path, e))?;
+        // the files inside a tar are always shown with their name
+        let many = entries.iter().any(|(name, _)| name != path);
+        let mut out = String::new();
+        let mut stats = Stats::default();
+        for (name, data) in entries {
+            let (text, s) = self.entry(&name, &data, self.with_filename || many)?;
+            out.push_str(&text);
+            stats += s;
+        }
+        Ok((out, stats))
+    }
+
+    // "path" is what is shown to the user: for the files inside an archive, it is "archive:inner/path"
+    fn entry(
+        &self,
+        path: &str,
+        bytes: &[u8],
+        with_filename: bool,
+    ) -> Result<(String, Stats), String> {
+        let started = Instant::now();
+        let mut decoded = decode::decode(bytes, self.encoding);
+        let binary_offset = decoded.binary_offset.filter(|_| !self.text);
+        // the whole content is normalized, so the offsets and the printed lines are the normalized ones
+        let content = match self.normalize {
+            Some(form) => normalize(&decoded.text, form),
+            None => std::mem::take(&mut decoded.text),
+        };
+
+        if let Some(table) = &self.table {
+            if binary_offset.is_some() {
+                return Err(format!(
+                    "{}: binary file, not a table (-a reads it anyway)",
+                    path
+                ));
+            }
+            let (out, records, matches) = self.records(table, path, &content, with_filename)?;
+            let stats = Stats {
+                elapsed: started.elapsed(),
+                searches: 1,
+                searches_with_match: usize::from(records > 0),
+                bytes_searched: bytes.len(),
+                matched_lines: records,
+                matches,
+            };
+            return Ok((out, stats));
+        }
+
+        let mut res = match (&self.expr, &self.fuzzy, &self.searcher) {
+            (Some(e), _, _) => {
+                search_expr(&self.ac, e, self.patterns.len(), &content, self.paragraph)
+            }
+            (None, Some(f), _) => search_fuzzy(f, &content),
+            (None, None, Some(s)) => search_with(s.as_ref(), &content),
+            (None, None, None) if self.multiline => search_multiline(&self.ac, &content),
+            (None, None, None) => search_patterns(&self.ac, &content),
+        };
+        if let Some(max) = self.max_count {
+            res.truncate(max);
+        }
+        let stats = Stats {
+            elapsed: started.elapsed(),
+            searches: 1,
+            searches_with_match: usize::from(!res.is_empty()),
+            bytes_searched: bytes.len(),
+            matched_lines: res.len(),
+            matches: res.iter().map(|m| non_overlapping(&m.matches).len()).sum(),
+        };
+
+        if let Some(at) = binary_offset {
+            if res.is_empty() {
+                return Ok((String::new(), stats));
+            }
+            let out = if self.json {
+                json::begin(path) + &json::end(path, Some(at), &stats)
+            } else {
+                format!(
+                    "{}: binary file matches (found \"\\0\" byte around offset {})\n",
+                    path, at
+                )
+            };
+            return Ok((out, stats));
+        }
+
+        if let (true, Some(with)) = (self.in_place, &self.replace) {
+            if res.is_empty() {
+                return Ok((String::new(), stats));
+            }
+            if decoded.lossy {
+                return Err(format!(
+                    "{}: not rewritten, it has bytes that aren't valid {:?}",
+                    path, decoded.encoding
+                ));
+            }
+            // from the start of the line to the start of the content
+            let spans: Vec<(usize, usize)> = res
+                .iter()
+                .flat_map(|m| {
+                    non_overlapping(&m.matches)
+                        .into_iter()
+                        .map(move |s| (m.offset + s.start, m.offset + s.end))
+                })
+                .collect();
+            let changed = replace::replace_spans(&content, &spans, with);
+            // written back in the encoding the file had
+            let changed =
+                decode::encode(&decoded, &changed).map_err(|e| format!("{}: {}", path, e))?;
+            replace::write_atomic(Path::new(path), &changed, self.backup)
+                .map_err(|e| format!("{}: {}", path, e))?;
+            let out = format!("{}: {} replacement(s)\n", path, spans.len());
+            return Ok((out, stats));
+        }
+
+        if self.multiline {
+            return Ok((self.blocks(path, &res, with_filename, &stats), stats));
+        }
+
+        let lines: Vec<(usize, &str)> = line_spans(&content).collect();
+
+        // the lines to print, by index: Some for the matches, None for their context
+        let mut printed: BTreeMap<usize, Option<&LineMatch>> = BTreeMap::new();
+        for m in &res {
+            let i = m.line_number - 1;
+            let last = (i + self.context).min(lines.len() - 1);
+            for j in i.saturating_sub(self.context)..=last {
+                printed.entry(j).or_insert(None);
+            }
+            printed.insert(i, Some(m));
+        }
+
+        let mut out = String::new();
+        if self.json && !res.is_empty() {
+            out.push_str(&json::begin(path));
+        }
+        let mut previous: Option<usize> = None;
+        for (&i, m) in &printed {
+            // like grep, "--" separates the groups of lines that aren't next to each other
+            // and so does it between paragraphs
+            if !self.json
+                && (self.context > 0 || self.paragraph)
+                && previous.is_some_and(|p| p + 1 < i)
+            {
+                out.push_str("--\n");
+            }
+            previous = Some(i);
+
+            let (offset, line) = lines[i];
+            if self.json {
+                let (kind, spans) = match m {
+                    Some(m) => ("match", non_overlapping(&m.matches)),
+                    None => ("context", vec![]),
+                };
+                out.push_str(&json::line(
+                    kind,
+                    path,
+                    line,
+                    i + 1,
+                    offset,
+                    &spans,
+                    self.fuzzy.is_some(),
+                ));
+                continue;
+            }
+
+            // grep separates the prefixes of matches with ':' and the ones of context lines with '-'
+            let sep = if m.is_some() { ':' } else { '-' };
+            if with_filename {
+                out.push_str(&format!("{}{}", path, sep));
+            }
+            if self.line_number {
+                out.push_str(&format!("{}{}", i + 1, sep));
+            }
+            match m {
+                Some(m) => self.matched_line(&mut out, line, &m.matches),
+                None => out.push_str(line),
+            }
+            out.push('\n');
+        }
+        if self.json && !res.is_empty() {
+            out.push_str(&json::end(path, None, &stats));
+        }
+        Ok((out, stats))
+    }
+
+    // what follows the prefixes of a matching line: its column, tags and text (replaced, maybe)
+    fn matched_line(&self, out: &mut String, line: &str, matches: &[Match]) {
+        // a line matching an expression can have no match at all, e.g. with "NOT test"
+        if self.column {
+            let at = matches.first().map_or(0, |h| h.start);
+            out.push_str(&format!("{}:", column(line, at)));
+        }
+        if self.fuzzy.is_some() {
+            // the text of every approximate match, with its distance from the pattern
+            let hit: Vec<String> = non_overlapping(matches)
+                .iter()
+                .map(|h| format!("{}~{}", &line[h.start..h.end], h.distance))
+                .collect();
+            out.push_str(&format!("[{}] ", hit.join(", ")));
+        } else if self.patterns.len() > 1 {
+            // with many patterns, tell the user which ones were found in the line
+            let mut ids: Vec<usize> = matches.iter().map(|h| h.pattern).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            let hit: Vec<&str> = ids.iter().map(|&id| self.patterns[id].as_str()).collect();
+            // none at all for a line matching "NOT x"
+            if !hit.is_empty() {
+                out.push_str(&format!("[{}] ", hit.join(", ")));
+            }
+        }
+        match &self.replace {
+            Some(with) => out.push_str(&replace::replace_line(line, matches, with)),
+            None => out.push_str(line),
+        }
+    }
+
+    // a line read by "--follow", if it matches
+    fn followed(&self, path: &str, line_number: usize, line: &str) -> Option<String> {
+        // decoded by the follower, but normalized like in "entry"
+        let line = match self.normalize {
+            Some(form) => normalize(line, form),
+            None => line.to_string(),
+        };
+        let mut matches = self.ac.find_iter(&line);
+        if matches.is_empty() {
+            return None;
+        }
+        if !self.text && line.contains('\0') {
+            return Some(format!(
+                "{}: binary line matches (found \"\\0\" byte)\n",
+                path
             ));
         }
-        // clone not efficient because it copies data, but easier than using lifetimes for now
-        let query = args[1].clone();
-        let filename = args[2].clone();
-        let case_sensitive = env::var("CASE_SENSITIVE").is_ok();
+        matches.sort_by_key(|m| (m.start, m.pattern));
+        let mut out = String::new();
+        if self.with_filename {
+            out.push_str(&format!("{}:", path));
+        }
+        if self.line_number {
+            out.push_str(&format!("{}:", line_number));
+        }
+        self.matched_line(&mut out, &line, &matches);
+        out.push('\n');
+        Some(out)
+    }
+
+    /*
+     * The output of "--csv" and "--tsv": the header, then every record where a pattern is found in the
+     * searched column, both with the selected columns only. The output is a table too:
+     *   minigrep --csv --field remarks --select flight,alt BRYTO flights.csv > bryto.csv
+     * Returns the output, the number of matching records and of matches.
+     */
+    fn records(
+        &self,
+        table: &Table,
+        path: &str,
+        content: &str,
+        with_filename: bool,
+    ) -> Result<(String, usize, usize), String> {
+        let records =
+            csv::parse(content, table.delimiter).map_err(|e| format!("{}: {}", path, e))?;
+        let Some((header, rows)) = records.split_first() else {
+            return Ok((String::new(), 0, 0));
+        };
+        let resolve =
+            |c: &Column| csv::resolve(&header.fields, c).map_err(|e| format!("{}: {}", path, e));
+        let field = table.field.as_ref().map(resolve).transpose()?;
+        let selected: Vec<usize> = if table.select.is_empty() {
+            (0..header.fields.len()).collect()
+        } else {
+            table.select.iter().map(resolve).collect::<Result<_, _>>()?
+        };
+
+        let mut found = vec![];
+        let mut matches = 0;
+        for r in rows {
+            if self.max_count.is_some_and(|max| found.len() >= max) {
+                break;
+            }
+            // a record can have fewer fields than the header: the missing ones are empty
+            let searched: Vec<&String> = match field {
+                Some(i) => r.fields.get(i).into_iter().collect(),
+                None => r.fields.iter().collect(),
+            };
+            let n: usize = searched
+                .iter()
+                .map(|f| non_overlapping(&self.ac.find_iter(f)).len())
+                .sum();
+            if n > 0 {
+                found.push(r);
+                matches += n;
+            }
+        }
+        if found.is_empty() {
+            return Ok((String::new(), 0, 0));
+        }
+
+        let mut out = String::new();
+        for r in std::iter::once(header).chain(found.iter().copied()) {
+            if with_filename {
+                out.push_str(&format!("{}:", path));
+            }
+            if self.line_number {
+                out.push_str(&format!("{}:", r.line));
+            }
+            let fields: Vec<&str> = selected
+                .iter()
+                .map(|&i| r.fields.get(i).map_or("", String::as_str))
+                .collect();
+            out.push_str(&csv::write(&fields, table.delimiter));
+            out.push('\n');
+        }
+        Ok((out, found.len(), matches))
+    }
+
+    /*
+     * The output of "--multiline": every block of lines covered by matches, with the range of its lines
+     * before the first one, e.g. for a stack trace:
+     *   12-14:java.lang.NullPointerException
+     *       at Engine.start(Engine.java:42)
+     *       at Main.main(Main.java:7)
+     */
+    fn blocks(&self, path: &str, res: &[LineMatch], with_filename: bool, stats: &Stats) -> String {
+        let mut out = String::new();
+        if self.json && !res.is_empty() {
+            out.push_str(&json::begin(path));
+        }
+        for (n, m) in res.iter().enumerate() {
+            let matches = non_overlapping(&m.matches);
+            if self.json {
+                // like ripgrep: "lines" is the whole block and "line_number" its first line
+                out.push_str(&json::line(
+                    "match",
+                    path,
+                    m.line,
+                    m.line_number,
+                    m.offset,
+                    &matches,
+                    false,
+                ));
+                continue;
+            }
 
-        Ok(Config { query, filename, case_sensitive })
+            if n > 0 {
+                out.push_str("--\n");
+            }
+            if with_filename {
+                out.push_str(&format!("{}:", path));
+            }
+            let last = m.line_number + m.line.matches('\n').count();
+            out.push_str(&format!("{}-{}:", m.line_number, last));
+            if self.column {
+                out.push_str(&format!("{}:", column(m.line, matches[0].start)));
+            }
+            match &self.replace {
+                Some(with) => out.push_str(&replace::replace_line(m.line, &m.matches, with)),
+                None => out.push_str(m.line),
+            }
+            out.push('\n');
+        }
+        if self.json && !res.is_empty() {
+            out.push_str(&json::end(path, None, stats));
+        }
+        out
     }
 }
 
+pub struct LineMatch<'a> {
+    // starting from 1
+    pub line_number: usize,
+    // byte offset of the start of the line in the content
+    pub offset: usize,
+    // with "--multiline", many lines joined by '\n'
+    pub line: &'a str,
+    // every occurrence found in the line, with the pattern that produced it
+    pub matches: Vec<Match>,
+}
+
+// every line is read once, no matter how many patterns the automaton has been built with
+pub fn search_patterns<'a>(ac: &AhoCorasick, content: &'a str) -> Vec<LineMatch<'a>> {
+    search_lines(content, |l| {
+        let mut matches = ac.find_iter(l);
+        matches.sort_by_key(|m| (m.start, m.pattern));
+        matches
+    })
+}
+
+pub fn search_fuzzy<'a>(f: &Fuzzy, content: &'a str) -> Vec<LineMatch<'a>> {
+    search_lines(content, |l| f.find_iter(l))
+}
+
+/*
+ * The occurrences of the needle are looked for in the whole content, not line by line: see searcher.rs.
+ * The needle mustn't be empty.
+ */
+pub fn search_with<'a>(s: &dyn Searcher, content: &'a str) -> Vec<LineMatch<'a>> {
+    let lines: Vec<(usize, &str)> = line_spans(content).collect();
+    let h = content.as_bytes();
+    let n = s.needle().len();
+    let mut res: Vec<LineMatch> = vec![];
+    let mut pos = 0;
+    while let Some(at) = s.find(&h[pos..]) {
+        let start = pos + at;
+        pos = start + n;
+        let i = lines.partition_point(|&(o, _)| o <= start) - 1;
+        let (offset, line) = lines[i];
+        // a needle with a '\n' (or the '\r' of "\r\n") would go past the end of the line
+        if start + n > offset + line.len() {
+            continue;
+        }
+        let m = Match {
+            pattern: 0,
+            start: start - offset,
+            end: start - offset + n,
+            distance: 0,
+        };
+        match res.last_mut() {
+            Some(last) if last.line_number == i + 1 => last.matches.push(m),
+            _ => res.push(LineMatch {
+                line_number: i + 1,
+                offset,
+                line,
+                matches: vec![m],
+            }),
+        }
+    }
+    res
+}
+
+// the lines where "find" returns at least one match
+fn search_lines<'a>(content: &'a str, find: impl Fn(&str) -> Vec<Match>) -> Vec<LineMatch<'a>> {
+    let mut res = vec![];
+    for (i, (offset, l)) in line_spans(content).enumerate() {
+        let matches = find(l);
+        if !matches.is_empty() {
+            res.push(LineMatch {
+                line_number: i + 1,
+                offset,
+                line: l,
+                matches,
+            });
+        }
+    }
+    res
+}
+
+/*
+ * The lines, or with "paragraph" the groups of consecutive non blank lines, where the terms found make
+ * the expression true. Every line of a matching paragraph is returned, even the ones without any term.
+ */
+pub fn search_expr<'a>(
+    ac: &AhoCorasick,
+    expr: &Expr,
+    terms: usize,
+    content: &'a str,
+    paragraph: bool,
+) -> Vec<LineMatch<'a>> {
+    let mut lines: Vec<LineMatch> = line_spans(content)
+        .enumerate()
+        .map(|(i, (offset, l))| {
+            let mut matches = ac.find_iter(l);
+            matches.sort_by_key(|m| (m.start, m.pattern));
+            LineMatch {
+                line_number: i + 1,
+                offset,
+                line: l,
+                matches,
+            }
+        })
+        .collect();
+    if paragraph {
+        // blank lines are not part of any paragraph, so they are never returned
+        lines.retain(|l| !l.line.trim().is_empty());
+    }
+
+    let mut res = vec![];
+    let mut rest = lines.into_iter().peekable();
+    while let Some(first) = rest.next() {
+        let mut window = vec![first];
+        while paragraph
+            && rest
+                .peek()
+                .is_some_and(|l| l.line_number == window[window.len() - 1].line_number + 1)
+        {
+            window.extend(rest.next());
+        }
+
+        let mut found = vec![false; terms];
+        for m in window.iter().flat_map(|l| &l.matches) {
+            found[m.pattern] = true;
+        }
+        if expr.eval(&found) {
+            res.extend(window);
+        }
+    }
+    res
+}
+
+/*
+ * The whole content is searched at once, so a pattern containing '\n' can match across lines.
+ * The matches covering the same lines (or lines next to each other) are grouped into one block:
+ * a LineMatch whose "line" is all of those lines and whose matches are relative to its start.
+ */
+pub fn search_multiline<'a>(ac: &AhoCorasick, content: &'a str) -> Vec<LineMatch<'a>> {
+    let mut matches = ac.find_iter(content);
+    // an empty file has no line to show, even for the empty pattern matching in it
+    if content.is_empty() || matches.is_empty() {
+        return vec![];
+    }
+    let lines: Vec<(usize, &str)> = line_spans(content).collect();
+    // the line containing the byte at "offset"
+    let line_of = |offset: usize| {
+        lines
+            .partition_point(|&(start, _)| start <= offset)
+            .saturating_sub(1)
+    };
+
+    matches.sort_by_key(|m| (m.start, m.pattern));
+    // first line, last line and matches of every block
+    let mut blocks: Vec<(usize, usize, Vec<Match>)> = vec![];
+    for m in matches {
+        let first = line_of(m.start);
+        // a match ending with '\n' ends where the next line starts: that line is shown too
+        let last = line_of(m.end.min(content.len() - 1)).max(first);
+        match blocks.last_mut() {
+            Some((_, block_last, ms)) if first <= *block_last + 1 => {
+                *block_last = last.max(*block_last);
+                ms.push(m);
+            }
+            _ => blocks.push((first, last, vec![m])),
+        }
+    }
+
+    blocks
+        .into_iter()
+        .map(|(first, last, ms)| {
+            let start = lines[first].0;
+            let end = lines[last].0 + lines[last].1.len();
+            let block = &content[start..end];
+            LineMatch {
+                line_number: first + 1,
+                offset: start,
+                line: block,
+                // the '\n' (or "\r\n") ending the block isn't part of it
+                matches: ms
+                    .into_iter()
+                    .map(|m| Match {
+                        start: m.start - start,
+                        end: (m.end - start).min(block.len()),
+                        ..m
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// "\n" is a newline in the patterns of "--multiline" (and "\\" a backslash), easier to type than a real one
+pub fn unescape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// like str::lines, but every line comes with the byte offset where it starts in content
+pub fn line_spans(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    content.split_inclusive('\n').map(move |l| {
+        let start = offset;
+        offset += l.len();
+        let l = match l.strip_suffix('\n') {
+            Some(l) => l.strip_suffix('\r').unwrap_or(l),
+            None => l,
+        };
+        (start, l)
+    })
+}
+
+// Aho-Corasick reports overlapping matches: keep the leftmost ones, the longest first
+pub fn non_overlapping(matches: &[Match]) -> Vec<Match> {
+    let mut sorted = matches.to_vec();
+    sorted.sort_by_key(|m| (m.start, std::cmp::Reverse(m.end)));
+    let mut res: Vec<Match> = vec![];
+    for m in sorted {
+        if res.last().is_none_or(|last| m.start >= last.end) {
+            res.push(m);
+        }
+    }
+    res
+}
+
 pub fn search_case_sensitive<'a>(query: &str, content: &'a str) -> Vec<&'a str> {
     let mut res = vec![];
     for l in content.lines() {
@@ -64,16 +907,33 @@ pub fn search_case_sensitive<'a>(query: &str, content: &'a str) -> Vec<&'a str>
 }
 
 pub fn search<'a>(query: &str, content: &'a str) -> Vec<&'a str> {
+    // the query is folded once, the lines are folded char by char while they are read: no String per line
+    let ac = AhoCorasick::new(&[query], true);
     let mut res = vec![];
     for l in content.lines() {
-        // l and query are not being modified as "to_lowercase" returns a String
-        if l.to_lowercase().contains(&query.to_lowercase()) {
+        if ac.is_match(l) {
             res.push(l)
         }
     }
     res
 }
 
+pub fn normalize(s: &str, form: Normalization) -> String {
+    match form {
+        Normalization::Nfc => s.nfc().collect(),
+        Normalization::Nfd => s.nfd().collect(),
+    }
+}
+
+/*
+ * The column (starting from 1) of the byte offset "at" in "line", counted in graphemes, i.e. what the user
+ * perceives as a single character: "e" followed by a combining accent is 2 chars but 1 grapheme, and so
+ * is a flag emoji made of 2 regional indicators.
+ */
+pub fn column(line: &str, at: usize) -> usize {
+    line[..at].graphemes(true).count() + 1
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -91,9 +951,137 @@ mod test {
         let query = "ME";
         let content = "hey\nit's me\nnot mr. MEME";
 
+        assert_eq!(vec!["it's me", "not mr. MEME"], search(query, content));
+    }
+
+    #[test]
+    fn many_patterns() {
+        let content = "N123AB cleared\nN456CD holding\nn123ab and N456CD";
+        let ac = AhoCorasick::new(&["N123AB", "N456CD"], true);
+
+        let res = search_patterns(&ac, content);
+        assert_eq!(
+            vec![1, 2, 3],
+            res.iter().map(|m| m.line_number).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0, 1],
+            res[2].matches.iter().map(|m| m.pattern).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let content = "hey\r\nit's me\n\nme";
+
+        assert_eq!(
+            vec![(0, "hey"), (5, "it's me"), (13, ""), (14, "me")],
+            line_spans(content).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            line_spans(content).map(|(_, l)| l).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn leftmost_longest() {
+        let ac = AhoCorasick::new(&["he", "she", "hers"], false);
+        let spans: Vec<(usize, usize)> = non_overlapping(&ac.find_iter("ushers"))
+            .iter()
+            .map(|m| (m.start, m.end))
+            .collect();
+        assert_eq!(vec![(1, 4)], spans);
+    }
+
+    #[test]
+    fn unicode_case_insensitive() {
+        let content = "Straße 1\nSTRASSE 2\nΣΟΦΟΣ\nother";
+
+        assert_eq!(vec!["Straße 1", "STRASSE 2"], search("strasse", content));
+        assert_eq!(vec!["ΣΟΦΟΣ"], search("σοφος", content));
+    }
+
+    #[test]
+    fn normalized_forms() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+
+        assert!(search(composed, decomposed).is_empty());
+        let nfc = normalize(decomposed, Normalization::Nfc);
+        assert_eq!(vec![composed], search(composed, &nfc));
+    }
+
+    #[test]
+    fn expressions() {
+        let content = "fuel low\nengine fire test\n\nfuel check\nlow on fuel\nok\n\nengine fire";
+        let q = expr::parse(r#"(fuel AND low) OR "engine fire" NOT test"#).unwrap();
+        let ac = AhoCorasick::new(&q.terms, true);
+
+        let lines = |paragraph| -> Vec<usize> {
+            search_expr(&ac, &q.expr, q.terms.len(), content, paragraph)
+                .iter()
+                .map(|m| m.line_number)
+                .collect()
+        };
+        assert_eq!(vec![1, 5, 8], lines(false));
+        // the first paragraph matches thanks to its first line, the second one as a whole
+        assert_eq!(vec![1, 2, 4, 5, 6, 8], lines(true));
+
+        let q = expr::parse("NOT fuel").unwrap();
+        let ac = AhoCorasick::new(&q.terms, true);
+        let res = search_expr(&ac, &q.expr, q.terms.len(), "fuel\nok", false);
+        assert_eq!(vec!["ok"], res.iter().map(|m| m.line).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn multiline_blocks() {
+        let content = "ok\nError: boom\n  at a\n  at b\nok\nError: again\n  at c\n";
+        let ac = AhoCorasick::new(
+            &[unescape("Error: boom\\n  at"), unescape("again\\n")],
+            true,
+        );
+
+        let res = search_multiline(&ac, content);
+        let blocks: Vec<(usize, &str)> = res.iter().map(|m| (m.line_number, m.line)).collect();
         assert_eq!(
-            vec!["it's me", "not mr. MEME"],
-            search(query, content)
+            vec![(2, "Error: boom\n  at a"), (6, "Error: again\n  at c")],
+            blocks
         );
+        assert_eq!((7, 13), (res[1].matches[0].start, res[1].matches[0].end));
+        assert_eq!("a\\b", unescape("a\\\\b"));
+
+        // the empty pattern in an empty file: no line, and no panic
+        let ac = AhoCorasick::new(&[String::new()], true);
+        assert!(search_multiline(&ac, "").is_empty());
+    }
+
+    #[test]
+    fn searchers_find_the_same_matches() {
+        let content = "DCT BRYTO\r\nCLEARED\nBRYTO BRYTO\nBRYT\nO";
+        let spans = |res: Vec<LineMatch>| -> Vec<(usize, usize, usize)> {
+            res.iter()
+                .flat_map(|m| m.matches.iter().map(|s| (m.line_number, s.start, s.end)))
+                .collect()
+        };
+        let expected = spans(search_patterns(
+            &AhoCorasick::new(&["BRYTO"], false),
+            content,
+        ));
+        assert_eq!(vec![(1, 4, 9), (3, 0, 5), (3, 6, 11)], expected);
+        for s in searcher::Strategy::ALL {
+            assert_eq!(
+                expected,
+                spans(search_with(s.build("BRYTO").as_ref(), content))
+            );
+        }
+    }
+
+    #[test]
+    fn grapheme_columns() {
+        let line = "cafe\u{301} 🇮🇹 tell";
+
+        assert_eq!(1, column(line, 0));
+        assert_eq!(8, column(line, line.find("tell").unwrap()));
     }
 }
@@ -1,17 +1,49 @@
 use std::{env, process};
 
-use minigrep::{run, Config};
+use minigrep::{help, parse, run, run_index, version, Command};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        eprintln!("problem parsing arguments: {}", err);
-        process::exit(1)
-    });
+    let config = match parse(&args) {
+        Ok(Command::Search(config)) => *config,
+        Ok(Command::Index(dirs)) => {
+            if let Err(e) = run_index(dirs) {
+                eprintln!("something went wrong indexing: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Help) => {
+            print!("{}", help());
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("{}", version());
+            return;
+        }
+        Err(err) => {
+            eprintln!("problem parsing arguments: {}", err);
+            eprintln!("run with --help to see the available flags");
+            process::exit(2)
+        }
+    };
 
-    println!("searching for {}", config.query);
-    println!("in {}", config.filename);
+    // the JSON output must only contain JSON objects, and the one of --csv/--tsv only records.
+    // With --interactive, there is no query yet
+    if !config.json && config.table.is_none() && !config.interactive {
+        if let Some(q) = &config.expr {
+            println!("searching for {}", q.text);
+        } else if config.pattern_files.is_empty() {
+            println!("searching for {}", config.patterns.join(", "));
+        } else {
+            println!(
+                "searching for the patterns in {}",
+                config.pattern_files.join(", ")
+            );
+        }
+        println!("in {}", config.files.join(", "));
+    }
 
     if let Err(e) = run(config) {
         eprintln!("something went wrong reading the file: {}", e);
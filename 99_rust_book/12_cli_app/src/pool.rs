@@ -0,0 +1,128 @@
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+/*
+ * A tiny worker pool: "threads" workers share the queue of jobs through Arc<Mutex<..>> (like the counter
+ * in 16_3_threads_state) and send every result back on a mpsc channel (like 12_concurrency).
+ *
+ * Each result comes back with the index of its job, so the caller can tell them apart even though they
+ * arrive in whatever order the workers finish. A job that panics gives back the panic message as an
+ * Err instead of its result: without that, the worker would die with it and its result would just be
+ * missing, which nobody would notice.
+ */
+pub fn spawn<T, R, F>(
+    jobs: Vec<T>,
+    threads: usize,
+    f: F,
+) -> mpsc::Receiver<(usize, Result<R, String>)>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> R + Send + Sync + 'static,
+{
+    let queue: VecDeque<(usize, T)> = jobs.into_iter().enumerate().collect();
+    let threads = threads.clamp(1, queue.len().max(1));
+    let queue = Arc::new(Mutex::new(queue));
+    let f = Arc::new(f);
+    let (tx, rx) = mpsc::channel();
+
+    for _ in 0..threads {
+        let queue = Arc::clone(&queue);
+        let f = Arc::clone(&f);
+        let tx = tx.clone();
+        thread::spawn(move || loop {
+            // the guard is dropped at the end of this statement, so the lock isn't held while f runs
+            let job = queue.lock().unwrap().pop_front();
+            let Some((i, job)) = job else { break };
+            // AssertUnwindSafe: the job is gone with the panic, and f must keep no state it could leave
+            // half changed (the searches keep none)
+            let res = panic::catch_unwind(AssertUnwindSafe(|| f(job))).map_err(panic_message);
+            if tx.send((i, res)).is_err() {
+                break; // nobody is listening anymore
+            }
+        });
+    }
+
+    // the receiver stops iterating once every worker has dropped its sender: ours must go too
+    drop(tx);
+    rx
+}
+
+/*
+ * Hands out the results of "spawn" in job order: a result that arrives early is kept aside until all
+ * the ones before it have been handed out.
+ */
+pub fn in_order<R>(rx: impl IntoIterator<Item = (usize, R)>) -> impl Iterator<Item = R> {
+    let mut pending = HashMap::new();
+    let mut next = 0;
+    let mut rx = rx.into_iter();
+    std::iter::from_fn(move || loop {
+        if let Some(r) = pending.remove(&next) {
+            next += 1;
+            return Some(r);
+        }
+        let (i, r) = rx.next()?;
+        pending.insert(i, r);
+    })
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(s) => *s,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => String::from("unknown panic"),
+        },
+    }
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_job_runs_once() {
+        let mut res: Vec<(usize, usize)> = spawn((0..100).collect(), 8, |n: usize| n * 2)
+            .into_iter()
+            .map(|(i, r)| (i, r.unwrap()))
+            .collect();
+        res.sort();
+        assert_eq!(100, res.len());
+        assert!(res.iter().all(|&(i, r)| r == i * 2));
+    }
+
+    #[test]
+    fn ordered_results() {
+        let rx = spawn((0..50).rev().collect(), 4, |n: u64| {
+            thread::sleep(std::time::Duration::from_millis(n % 5));
+            n
+        });
+        assert_eq!(
+            (0..50).rev().collect::<Vec<_>>(),
+            in_order(rx).map(Result::unwrap).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn panics_are_results() {
+        let rx = spawn((0..20).collect(), 4, |n: u32| {
+            if n == 3 {
+                panic!("job {} failed", n);
+            }
+            n
+        });
+        let res: Vec<Result<u32, String>> = in_order(rx).collect();
+        assert_eq!(20, res.len());
+        assert_eq!(Err(String::from("job 3 failed")), res[3]);
+        assert_eq!(Ok(19), res[19]);
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{aho_corasick::Match, non_overlapping};
+
+/*
+ * "--replace TEXT" substitutes TEXT to every match. There is no regex support yet, so TEXT is
+ * always taken literally ("$1" is just two chars): capture groups need a regex engine to exist.
+ */
+
+// "matches" are relative to "line", like the ones in LineMatch
+pub fn replace_line(line: &str, matches: &[Match], with: &str) -> String {
+    let spans: Vec<(usize, usize)> = non_overlapping(matches)
+        .iter()
+        .map(|m| (m.start, m.end))
+        .collect();
+    replace_spans(line, &spans, with)
+}
+
+// "spans" must be sorted and must not overlap
+pub fn replace_spans(text: &str, spans: &[(usize, usize)], with: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for &(start, end) in spans {
+        out.push_str(&text[last..start]);
+        out.push_str(with);
+        last = end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/*
+ * Replacing a file in place is done in two steps: the new content goes to a temporary file in the same
+ * directory, which is then renamed over the original. A rename within the same filesystem is atomic, so
+ * whoever reads the file sees either the old content or the new one, never half of each (which could
+ * happen if we truncated the original and the program crashed while writing).
+ *
+ * Every write has its own temporary file (named after the process and a counter), or two minigreps
+ * rewriting the same file, or two threads, would write into the same one and rename each other's half.
+ * The rename itself is only on disk once the directory is, so the directory is synced too.
+ *
+ * With "backup", the original is first copied to "path.bak".
+ */
+pub fn write_atomic(path: &Path, content: &[u8], backup: bool) -> io::Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+        .to_string_lossy();
+    let n = WRITES.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_file_name(format!(".{}.minigrep.{}-{}.tmp", name, process::id(), n));
+
+    let res = (|| {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(content)?;
+        // make sure the data is on disk before the rename makes it visible
+        f.sync_all()?;
+        // a new file (there's no original) keeps the default permissions
+        if let Ok(meta) = fs::metadata(path) {
+            fs::set_permissions(&tmp, meta.permissions())?;
+        }
+        if backup {
+            fs::copy(path, backup_path(path))?;
+        }
+        fs::rename(&tmp, path)
+    })();
+
+    if res.is_err() {
+        // the original is untouched, only the temporary file must go
+        let _ = fs::remove_file(&tmp);
+    }
+    res?;
+    sync_dir(path)
+}
+
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all(),
+    }
+}
+
+// a directory can't be opened like a file on Windows, where renames don't need it anyway
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".bak");
+    PathBuf::from(p)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::aho_corasick::AhoCorasick;
+
+    #[test]
+    fn literal_replacement() {
+        let ac = AhoCorasick::new(&["BRITO", "BRIT"], true);
+        let line = "direct brito then BRITO";
+        assert_eq!(
+            "direct BRYTO then BRYTO",
+            replace_line(line, &ac.find_iter(line), "BRYTO")
+        );
+        assert_eq!("$1 x", replace_spans("ab x", &[(0, 2)], "$1"));
+    }
+
+    #[test]
+    fn in_place_with_backup() {
+        let dir = std::env::temp_dir().join(format!("minigrep-replace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("route.cfg");
+        fs::write(&path, "BRITO\r\nDCT\n").unwrap();
+
+        write_atomic(&path, b"BRYTO\r\nDCT\n", true).unwrap();
+
+        assert_eq!("BRYTO\r\nDCT\n", fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            "BRITO\r\nDCT\n",
+            fs::read_to_string(backup_path(&path)).unwrap()
+        );
+        // no temporary file left behind
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // the last write wins, and none of them fails on a temporary file another one renamed
+    #[test]
+    fn concurrent_writes() {
+        let dir =
+            std::env::temp_dir().join(format!("minigrep-replace-many-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("route.cfg");
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let path = &path;
+                s.spawn(move || {
+                    for i in 0..20 {
+                        write_atomic(path, format!("{} {}\n", t, i).as_bytes(), false).unwrap();
+                    }
+                });
+            }
+        });
+        assert!(fs::read_to_string(&path).unwrap().ends_with(" 19\n"));
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,460 @@
+/*
+ * Different ways of finding the lines that contain one case sensitive pattern (the "needle"), behind
+ * the same trait so that they can be picked at runtime ("--searcher") and compared ("cargo run --release
+ * -p minigrep --bin bench").
+ *
+ * - naive: a for loop over the lines and, in each one, a for loop over the positions (what the first
+ *   version of minigrep did)
+ * - iter: the same with iterator adapters (what "13_3_cli_app_iterators" did, before being merged here)
+ * - two-way: the Crochemore-Perrin algorithm, what str::contains uses, on the whole content at once
+ * - simd: compares the first and last byte of the needle at 32 positions at a time, written so that
+ *   the compiler can turn it into vector instructions
+ *
+ * The last two search the whole content instead of one line at a time: most lines don't match, so
+ * it's cheaper to find a match first and only then look for the line around it (with memchr).
+ */
+
+pub trait Searcher: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn needle(&self) -> &[u8];
+
+    // the offset of the first occurrence of the needle in haystack
+    fn find(&self, haystack: &[u8]) -> Option<usize>;
+
+    // the lines of content containing the needle, which mustn't contain '\n'
+    fn lines<'a>(&self, content: &'a str) -> Vec<&'a str> {
+        let h = content.as_bytes();
+        let mut res = vec![];
+        let mut pos = 0;
+        while pos < h.len() {
+            let Some(at) = self.find(&h[pos..]) else {
+                break;
+            };
+            let at = pos + at;
+            let start = memrchr(b'\n', &h[..at]).map_or(0, |i| i + 1);
+            let end = memchr(b'\n', &h[at..]).map_or(h.len(), |i| at + i);
+            // like str::lines, without the '\r' of "\r\n"
+            let line = &content[start..end];
+            res.push(line.strip_suffix('\r').unwrap_or(line));
+            pos = end + 1;
+        }
+        res
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Strategy {
+    Naive,
+    Iter,
+    TwoWay,
+    Simd,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 4] = [
+        Strategy::Naive,
+        Strategy::Iter,
+        Strategy::TwoWay,
+        Strategy::Simd,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Naive => "naive",
+            Strategy::Iter => "iter",
+            Strategy::TwoWay => "two-way",
+            Strategy::Simd => "simd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        Strategy::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    pub fn build(self, needle: &str) -> Box<dyn Searcher> {
+        let needle = needle.as_bytes().to_vec();
+        match self {
+            Strategy::Naive => Box::new(Naive { needle }),
+            Strategy::Iter => Box::new(Iter { needle }),
+            Strategy::TwoWay => Box::new(TwoWay::new(needle)),
+            Strategy::Simd => Box::new(Simd { needle }),
+        }
+    }
+}
+
+pub struct Naive {
+    needle: Vec<u8>,
+}
+
+impl Searcher for Naive {
+    fn name(&self) -> &'static str {
+        "naive"
+    }
+
+    fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    // every position is compared with the needle: O(haystack * needle) in the worst case
+    fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let n = self.needle.len();
+        if n > haystack.len() {
+            return None;
+        }
+        for i in 0..=haystack.len() - n {
+            let mut j = 0;
+            while j < n && haystack[i + j] == self.needle[j] {
+                j += 1;
+            }
+            if j == n {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    fn lines<'a>(&self, content: &'a str) -> Vec<&'a str> {
+        let mut res = vec![];
+        for l in content.lines() {
+            if self.find(l.as_bytes()).is_some() {
+                res.push(l);
+            }
+        }
+        res
+    }
+}
+
+pub struct Iter {
+    needle: Vec<u8>,
+}
+
+impl Searcher for Iter {
+    fn name(&self) -> &'static str {
+        "iter"
+    }
+
+    fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    fn find(&self, haystack: &[u8]) -> Option<usize> {
+        // windows(0) panics: the empty needle is everywhere
+        if self.needle.is_empty() {
+            return Some(0);
+        }
+        haystack
+            .windows(self.needle.len())
+            .position(|w| w == self.needle)
+    }
+
+    fn lines<'a>(&self, content: &'a str) -> Vec<&'a str> {
+        content
+            .lines()
+            .filter(|l| self.find(l.as_bytes()).is_some())
+            .collect()
+    }
+}
+
+/*
+ * Two-Way splits the needle in two halves at a "critical position", chosen so that after a mismatch
+ * the needle can be shifted far without missing any occurrence. The right half is compared left to
+ * right, then the left half right to left. It is O(haystack + needle) with O(1) extra memory.
+ * For periodic needles (e.g. "abab"), "memory" remembers how much of the needle is known to match
+ * after a shift by the period, so those bytes aren't compared twice.
+ */
+pub struct TwoWay {
+    needle: Vec<u8>,
+    crit_pos: usize,
+    period: usize,
+    // the period isn't the real one but a shift that is safe: "memory" can't be used
+    long_period: bool,
+}
+
+impl TwoWay {
+    fn new(needle: Vec<u8>) -> TwoWay {
+        if needle.is_empty() {
+            return TwoWay {
+                needle,
+                crit_pos: 0,
+                period: 1,
+                long_period: true,
+            };
+        }
+        // the critical position is the later of the maximal suffixes for both orders of the bytes
+        let (pos_less, period_less) = maximal_suffix(&needle, false);
+        let (pos_greater, period_greater) = maximal_suffix(&needle, true);
+        let (crit_pos, period) = if pos_less > pos_greater {
+            (pos_less, period_less)
+        } else {
+            (pos_greater, period_greater)
+        };
+
+        let n = needle.len();
+        if period + crit_pos <= n && needle[..crit_pos] == needle[period..period + crit_pos] {
+            TwoWay {
+                needle,
+                crit_pos,
+                period,
+                long_period: false,
+            }
+        } else {
+            TwoWay {
+                period: crit_pos.max(n - crit_pos) + 1,
+                needle,
+                crit_pos,
+                long_period: true,
+            }
+        }
+    }
+}
+
+// the start and the period of the maximal suffix of "s", in lexicographic order (or the reverse one)
+fn maximal_suffix(s: &[u8], greater: bool) -> (usize, usize) {
+    let (mut left, mut right, mut offset, mut period) = (0, 1, 0, 1);
+    while let Some(&a) = s.get(right + offset) {
+        let b = s[left + offset];
+        if (a < b && !greater) || (a > b && greater) {
+            // the suffix is smaller: its period is everything seen so far
+            right += offset + 1;
+            offset = 0;
+            period = right - left;
+        } else if a == b {
+            if offset + 1 == period {
+                right += offset + 1;
+                offset = 0;
+            } else {
+                offset += 1;
+            }
+        } else {
+            // the suffix is larger: it is the new candidate
+            left = right;
+            right += 1;
+            offset = 0;
+            period = 1;
+        }
+    }
+    (left, period)
+}
+
+impl Searcher for TwoWay {
+    fn name(&self) -> &'static str {
+        "two-way"
+    }
+
+    fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle;
+        let n = needle.len();
+        match n {
+            0 => return Some(0),
+            1 => return memchr(needle[0], haystack),
+            _ => {}
+        }
+
+        let mut pos = 0;
+        let mut memory = 0;
+        while pos + n <= haystack.len() {
+            // the right half, left to right
+            let start = if self.long_period {
+                self.crit_pos
+            } else {
+                self.crit_pos.max(memory)
+            };
+            let mut i = start;
+            while i < n && needle[i] == haystack[pos + i] {
+                i += 1;
+            }
+            if i < n {
+                pos += i - self.crit_pos + 1;
+                memory = 0;
+                continue;
+            }
+
+            // the left half, right to left
+            let stop = if self.long_period { 0 } else { memory };
+            let mut j = self.crit_pos;
+            while j > stop && needle[j - 1] == haystack[pos + j - 1] {
+                j -= 1;
+            }
+            if j <= stop {
+                return Some(pos);
+            }
+            pos += self.period;
+            if !self.long_period {
+                memory = n - self.period;
+            }
+        }
+        None
+    }
+}
+
+const LANES: usize = 32;
+
+/*
+ * For every block of 32 positions, a bit mask of the ones where both the first and the last byte of the
+ * needle are in the right place: it is computed with fixed size arrays and no branches, which the
+ * compiler vectorizes. Only these candidates (rarely more than one per block) are then fully compared.
+ */
+pub struct Simd {
+    needle: Vec<u8>,
+}
+
+impl Searcher for Simd {
+    fn name(&self) -> &'static str {
+        "simd"
+    }
+
+    fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    fn find(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle;
+        let n = needle.len();
+        if n == 0 {
+            return Some(0);
+        }
+        if n > haystack.len() {
+            return None;
+        }
+        let (first, last) = (needle[0], needle[n - 1]);
+        // the positions where the needle could start
+        let end = haystack.len() - n + 1;
+
+        let mut i = 0;
+        while i + LANES <= end {
+            let a: &[u8; LANES] = haystack[i..i + LANES].try_into().unwrap();
+            let b: &[u8; LANES] = haystack[i + n - 1..i + n - 1 + LANES].try_into().unwrap();
+            let mut mask = 0u32;
+            for k in 0..LANES {
+                mask |= (((a[k] == first) & (b[k] == last)) as u32) << k;
+            }
+            while mask != 0 {
+                let k = mask.trailing_zeros() as usize;
+                // with 1 or 2 bytes, the first and the last one are the whole needle
+                if n <= 2 || haystack[i + k + 1..i + k + n - 1] == needle[1..n - 1] {
+                    return Some(i + k);
+                }
+                // clears the lowest bit set
+                mask &= mask - 1;
+            }
+            i += LANES;
+        }
+        (i..end).find(|&p| haystack[p..p + n] == needle[..])
+    }
+}
+
+/*
+ * The position of the first "b" in haystack, reading 8 bytes at a time: a u64 where every byte is "b"
+ * is XORed with 8 bytes of the haystack, which makes the matching bytes 0, and "a zero byte in x" can
+ * be tested for all 8 of them at once with (x - 0x0101..) & !x & 0x8080.. (a trick known as SWAR,
+ * SIMD within a register).
+ */
+pub fn memchr(b: u8, haystack: &[u8]) -> Option<usize> {
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+    let repeated = LO * b as u64;
+    let mut chunks = haystack.chunks_exact(8);
+    let mut i = 0;
+    for c in &mut chunks {
+        let x = u64::from_le_bytes(c.try_into().unwrap()) ^ repeated;
+        if x.wrapping_sub(LO) & !x & HI != 0 {
+            break;
+        }
+        i += 8;
+    }
+    haystack[i..].iter().position(|&x| x == b).map(|p| i + p)
+}
+
+// the position of the last "b" in haystack
+pub fn memrchr(b: u8, haystack: &[u8]) -> Option<usize> {
+    haystack.iter().rposition(|&x| x == b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strategies_agree() {
+        let haystacks = [
+            "hey\nit's me\nnot mr. MEME\r\nme",
+            "abababababac abababac\naaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaab",
+            "DCT BRYTO CLEARED AS FILED, CLIMB FL350, SQUAWK 4521, DIRECT BRYTO",
+        ];
+        let needles = [
+            "me",
+            "M",
+            "abababac",
+            "aab",
+            "BRYTO",
+            "FL350,",
+            "x",
+            "",
+            "aaaaaaaaaab",
+        ];
+        for h in haystacks {
+            for n in needles {
+                let expected: Vec<&str> = h.lines().filter(|l| l.contains(n)).collect();
+                for s in Strategy::ALL {
+                    let searcher = s.build(n);
+                    assert_eq!(
+                        h.find(n),
+                        searcher.find(h.as_bytes()),
+                        "{} {:?}",
+                        s.name(),
+                        n
+                    );
+                    assert_eq!(expected, searcher.lines(h), "{} {:?}", s.name(), n);
+                }
+            }
+        }
+    }
+
+    // a small alphabet makes lots of periodic needles and partial matches
+    #[test]
+    fn random_haystacks() {
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut random = |n: usize| -> String {
+            (0..n)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    if seed.is_multiple_of(3) {
+                        'b'
+                    } else {
+                        'a'
+                    }
+                })
+                .collect()
+        };
+        for _ in 0..300 {
+            let h = random(100);
+            let n = random(1 + h.matches('b').count() % 9);
+            for s in Strategy::ALL {
+                assert_eq!(
+                    h.find(&n),
+                    s.build(&n).find(h.as_bytes()),
+                    "{} {} {}",
+                    s.name(),
+                    n,
+                    h
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn swar_memchr() {
+        let h = b"0123456789abcdef\nxyz";
+        assert_eq!(Some(16), memchr(b'\n', h));
+        assert_eq!(Some(19), memchr(b'z', h));
+        assert_eq!(None, memchr(b'!', h));
+        assert_eq!(Some(0), memchr(b'0', h));
+    }
+}
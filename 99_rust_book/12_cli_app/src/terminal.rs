@@ -0,0 +1,79 @@
+use std::io;
+
+/*
+ * The few things "--interactive" needs from the terminal, straight from termios.
+ *
+ * By default the terminal is in "cooked" mode: it echoes what is typed, lets the user edit the line and
+ * only sends it to the program on Enter, and turns Ctrl-C into a signal. In raw mode every key is sent
+ * as soon as it is pressed, untouched, and nothing is echoed: the program draws everything itself.
+ * The arrows and the other special keys arrive as escape sequences, e.g. "\x1b[A" for Up.
+ *
+ * The original settings are put back when the RawMode is dropped, even when the UI returns an error,
+ * otherwise the shell would be left unusable.
+ */
+
+pub struct RawMode {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        // SAFETY: termios is a plain C struct, filled by tcgetattr before being read
+        let mut t: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = t;
+        // no Ctrl-S/Ctrl-Q flow control, no translation of '\r' into '\n' on input or of '\n' into
+        // "\r\n" on output, no echo, no line editing, no signals
+        t.c_iflag &= !(libc::BRKINT | libc::ICRNL | libc::INPCK | libc::ISTRIP | libc::IXON);
+        t.c_oflag &= !libc::OPOST;
+        t.c_cflag |= libc::CS8;
+        t.c_lflag &= !(libc::ECHO | libc::ICANON | libc::IEXTEN | libc::ISIG);
+        // read() returns after a tenth of a second even if no key was pressed, so the UI can show the
+        // results of the search in the meantime
+        t.c_cc[libc::VMIN] = 0;
+        t.c_cc[libc::VTIME] = 1;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(RawMode { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
+
+// rows and columns of the terminal
+#[cfg(unix)]
+pub fn size() -> io::Result<(usize, usize)> {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } != 0 || ws.ws_row == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((usize::from(ws.ws_row), usize::from(ws.ws_col)))
+}
+
+#[cfg(not(unix))]
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw mode is only implemented on Unix",
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn size() -> io::Result<(usize, usize)> {
+    Ok((24, 80))
+}
@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use crate::{FixedClock, Policy, Post, PostError, Status, SystemClock, Timestamp, User, Verdict};
+
+/*
+ * The audit trail: instead of only swapping its state, a post appends an event to its log for
+ * every change, with who did it and when, and the state before and after.
+ *
+ * The log is enough to rebuild the post (event sourcing): Post::replay starts from the creation
+ * event and applies the actions again, one after the other, through the same code as the live
+ * calls. Every event is checked along the way: a log that was tampered with, where an event
+ * doesn't lead to the state it claims to, is refused.
+ */
+
+// the actor of the events caused by the clock, i.e. the publication of the scheduled posts
+pub const SCHEDULER: &str = "scheduler";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub at: Timestamp,
+    pub actor: User,
+    pub from: Status,
+    pub to: Status,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    // always the first event, with the policy the post was created with
+    Create(Policy),
+    AddText(String),
+    // the whole content, replaced
+    Edit(String),
+    // back to the content of an earlier revision, see revision.rs
+    Revert(usize),
+    RequestReview,
+    // an approval or a rejection, with the publication time of a scheduled approval
+    Review(Verdict, Option<Timestamp>),
+    PublishDue,
+}
+
+impl Action {
+    pub fn changes_content(&self) -> bool {
+        matches!(
+            self,
+            Action::AddText(_) | Action::Edit(_) | Action::Revert(_)
+        )
+    }
+
+    // for the error messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Create(_) => "create",
+            Action::AddText(_) => "add text",
+            Action::Edit(_) => "edit",
+            Action::Revert(_) => "revert",
+            Action::RequestReview => "request a review",
+            Action::Review(Verdict::Rejected(_), _) => "reject",
+            Action::Review(Verdict::Approved, Some(_)) => "schedule",
+            Action::Review(Verdict::Approved, None) => "approve",
+            Action::PublishDue => "publish",
+        }
+    }
+}
+
+impl Post {
+    pub fn replay(events: &[Event]) -> Result<Post, PostError> {
+        let invalid = |event: usize, reason: String| PostError::InvalidLog { event, reason };
+        let Some((first, rest)) = events.split_first() else {
+            return Err(invalid(0, String::from("there are no events")));
+        };
+        let Action::Create(policy) = &first.action else {
+            return Err(invalid(0, String::from("the post isn't created first")));
+        };
+
+        let mut post =
+            Post::with_clock(&first.actor, policy.clone(), Arc::new(FixedClock(first.at)));
+        for (i, e) in rest.iter().enumerate() {
+            let i = i + 1;
+            if post.status() != e.from {
+                return Err(invalid(
+                    i,
+                    format!("the post is {}, not {}", post.status(), e.from),
+                ));
+            }
+            post.check(&e.actor, &e.action)
+                .map_err(|err| invalid(i, err.to_string()))?;
+            post.apply(&e.actor, e.at, e.action.clone());
+            if post.log.len() != i + 1 {
+                return Err(invalid(i, String::from("it changes nothing")));
+            }
+            if post.status() != e.to {
+                return Err(invalid(
+                    i,
+                    format!("the post is {}, not {}", post.status(), e.to),
+                ));
+            }
+        }
+        // from now on, the events happen for real
+        post.clock = Arc::new(SystemClock);
+        Ok(post)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Role;
+
+    #[test]
+    fn replay() {
+        let ana = User::new("ana", &[Role::Author]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let pat = User::new("pat", &[Role::Publisher]);
+        let mut post = Post::with_clock(&ana, Policy::Approvals(2), Arc::new(FixedClock(10)));
+        post.add_text(&ana, "runway 27 closed").unwrap();
+        post.request_review(&ana).unwrap();
+        post.approve(&bob).unwrap();
+        // ignored, and so not logged
+        post.publish_due(&FixedClock(20));
+        post.schedule(&pat, 100).unwrap();
+        post.publish_due(&FixedClock(100));
+
+        let log = post.log();
+        let steps: Vec<(&str, Status, Status)> = log
+            .iter()
+            .map(|e| (e.actor.name.as_str(), e.from, e.to))
+            .collect();
+        assert_eq!(
+            vec![
+                ("ana", Status::Draft, Status::Draft),
+                ("ana", Status::Draft, Status::Draft),
+                ("ana", Status::Draft, Status::PendingReview),
+                ("bob", Status::PendingReview, Status::PendingReview),
+                ("pat", Status::PendingReview, Status::Scheduled),
+                (SCHEDULER, Status::Scheduled, Status::Published),
+            ],
+            steps
+        );
+        assert_eq!(100, log[5].at);
+
+        let copy = Post::replay(log).unwrap();
+        assert_eq!(Status::Published, copy.status());
+        assert_eq!("runway 27 closed", copy.content());
+        assert_eq!(log, copy.log());
+
+        // stopped in the middle of the review: the reviews given so far are back too
+        let copy = Post::replay(&log[..4]).unwrap();
+        assert_eq!(Status::PendingReview, copy.status());
+        assert_eq!("bob", copy.reviews()[0].reviewer.name);
+    }
+
+    #[test]
+    fn tampered_logs() {
+        let ana = User::new("ana", &[Role::Author, Role::Reviewer]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let mut post = Post::with_policy(&ana, Policy::Approvals(2));
+        post.request_review(&ana).unwrap();
+        post.approve(&bob).unwrap();
+
+        let mut log = post.log().to_vec();
+        log[2].to = Status::Scheduled;
+        assert!(matches!(
+            Post::replay(&log),
+            Err(PostError::InvalidLog { event: 2, .. })
+        ));
+        assert!(matches!(
+            Post::replay(&post.log()[1..]),
+            Err(PostError::InvalidLog { event: 0, .. })
+        ));
+        // the same approval twice
+        let mut log = post.log().to_vec();
+        log.push(log[2].clone());
+        assert_eq!(
+            Err(PostError::InvalidLog {
+                event: 3,
+                reason: String::from("bob has already reviewed this post")
+            }),
+            Post::replay(&log).map(|_| ())
+        );
+        // an approval the author gave themself, slipped into the log
+        let mut log = post.log().to_vec();
+        log[2].actor = ana.clone();
+        assert_eq!(
+            Err(PostError::InvalidLog {
+                event: 2,
+                reason: String::from("ana can't approve: nobody can approve their own post")
+            }),
+            Post::replay(&log).map(|_| ())
+        );
+    }
+}
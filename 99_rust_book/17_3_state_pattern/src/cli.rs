@@ -0,0 +1,497 @@
+use std::{env, error::Error, fmt, io::Read, path::PathBuf};
+
+use crate::{
+    store::{encode_policy, parse_policy, parse_role, parse_status, status_name},
+    Action, Clock, HtmlOptions, Policy, Post, PostError, PostStore, Status, StoreError, Timestamp,
+    User, Verdict, SCHEDULER,
+};
+
+/*
+ * The command line of the binary: every command loads the posts it needs from a PostStore, goes
+ * through the same Post methods as the library, and saves the posts it changed. The actor is
+ * given with "--as NAME:ROLES" (or the POSTS_AS variable): there is no login, whoever runs the
+ * command is trusted to say who they are, as with the author of a git commit.
+ *
+ * "run" returns what to print instead of printing it, so the tests can check it.
+ */
+
+pub const USAGE: &str = "\
+usage: state_pattern [--dir DIR] [--as NAME:ROLES] COMMAND
+
+commands:
+  new ID [--policy POLICY]    create a draft, e.g. --policy \"veto(approvals(2))\"
+  edit ID [TEXT]              replace the content of a draft (read from stdin without TEXT)
+  submit ID                   send a draft to review
+  approve ID
+  reject ID REASON
+  schedule ID TIME            approve, publishing at TIME: seconds since the epoch, or +N[smhd]
+  publish-due                 publish the scheduled posts whose time has come
+  list [--state STATE]        draft, pending-review, scheduled or published
+  show ID [--html]
+  log ID
+
+options:
+  --dir DIR           where the posts are (default: $POSTS_DIR, or \"posts\")
+  --as NAME:ROLES     who is acting, e.g. \"ana:author,reviewer\" (default: $POSTS_AS)
+                      roles: author, reviewer, publisher, admin
+";
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Store(StoreError),
+    // a transition the post refused, with the id of the post
+    Post { id: String, error: PostError },
+    Exists(String),
+    NotPublished(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Store(e) => write!(f, "{}", e),
+            CliError::Post { id, error } => write!(f, "{}: {}", id, error),
+            CliError::Exists(id) => write!(f, "there is already a post \"{}\"", id),
+            CliError::NotPublished(id) => write!(f, "{}: the post isn't published yet", id),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl From<StoreError> for CliError {
+    fn from(e: StoreError) -> Self {
+        CliError::Store(e)
+    }
+}
+
+fn usage(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+// the arguments, once the flags are out of the way
+struct Args {
+    dir: Option<String>,
+    actor: Option<String>,
+    policy: Option<String>,
+    state: Option<String>,
+    html: bool,
+    positional: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, CliError> {
+    let mut parsed = Args {
+        dir: None,
+        actor: None,
+        policy: None,
+        state: None,
+        html: false,
+        positional: vec![],
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| usage(format!("{} needs a value", arg)))
+        };
+        match arg.as_str() {
+            "--dir" => parsed.dir = Some(value()?),
+            "--as" => parsed.actor = Some(value()?),
+            "--policy" => parsed.policy = Some(value()?),
+            "--state" => parsed.state = Some(value()?),
+            "--html" => parsed.html = true,
+            "-h" | "--help" => parsed.positional.insert(0, String::from("help")),
+            // "-" is stdin, for edit
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(usage(format!("unknown flag {}", flag)))
+            }
+            _ => parsed.positional.push(arg.clone()),
+        }
+    }
+    Ok(parsed)
+}
+
+// "ana:author,reviewer"
+fn parse_user(text: &str) -> Result<User, CliError> {
+    let (name, roles) = text.split_once(':').unwrap_or((text, ""));
+    if name.is_empty() || name == SCHEDULER {
+        return Err(usage(format!("invalid user \"{}\"", text)));
+    }
+    let roles = roles
+        .split(',')
+        .filter(|r| !r.is_empty())
+        .map(parse_role)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(usage)?;
+    Ok(User::new(name, &roles))
+}
+
+// seconds since the epoch, or "+N" followed by s, m, h or d from now
+fn parse_time(text: &str, now: Timestamp) -> Result<Timestamp, CliError> {
+    let invalid = || usage(format!("invalid time \"{}\"", text));
+    let Some(relative) = text.strip_prefix('+') else {
+        return text.parse().map_err(|_| invalid());
+    };
+    let unit = match relative.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let n: Timestamp = relative[..relative.len() - 1]
+        .parse()
+        .map_err(|_| invalid())?;
+    n.checked_mul(unit)
+        .and_then(|d| now.checked_add(d))
+        .ok_or_else(invalid)
+}
+
+pub fn run(args: &[String], stdin: &mut dyn Read, clock: &dyn Clock) -> Result<String, CliError> {
+    let args = parse_args(args)?;
+    let Some((command, rest)) = args.positional.split_first() else {
+        return Err(usage("no command given"));
+    };
+    if command == "help" {
+        return Ok(USAGE.to_string());
+    }
+
+    let dir = args
+        .dir
+        .clone()
+        .or_else(|| env::var("POSTS_DIR").ok())
+        .unwrap_or_else(|| String::from("posts"));
+    let store = PostStore::open(PathBuf::from(dir))?;
+    let actor = || {
+        args.actor
+            .clone()
+            .or_else(|| env::var("POSTS_AS").ok())
+            .ok_or_else(|| usage(format!("{}: who are you? use --as NAME:ROLES", command)))
+            .and_then(|a| parse_user(&a))
+    };
+    // the arguments a command takes, after its name
+    let expect = |names: &[&str]| -> Result<(), CliError> {
+        if rest.len() == names.len() {
+            Ok(())
+        } else {
+            Err(usage(format!("usage: {} {}", command, names.join(" "))))
+        }
+    };
+    // a command that changes one post: it is saved only if the transition went through
+    let change = |f: &dyn Fn(&mut Post, &User) -> Result<(), PostError>| {
+        let (id, actor) = (&rest[0], actor()?);
+        let mut post = store.load(id)?;
+        f(&mut post, &actor).map_err(|error| CliError::Post {
+            id: id.clone(),
+            error,
+        })?;
+        store.save(id, &post)?;
+        Ok(format!("{}: {}\n", id, post.status()))
+    };
+
+    match command.as_str() {
+        "new" => {
+            expect(&["ID"])?;
+            let id = &rest[0];
+            let policy = match &args.policy {
+                Some(p) => parse_policy(p).map_err(usage)?,
+                None => Policy::default(),
+            };
+            if store.exists(id)? {
+                return Err(CliError::Exists(id.clone()));
+            }
+            store.save(id, &Post::with_policy(&actor()?, policy))?;
+            Ok(format!("{}: {}\n", id, Status::Draft))
+        }
+        "edit" => {
+            let text = match rest {
+                [_] => None,
+                [_, text] if text != "-" => Some(text.clone()),
+                [_, _] => None,
+                _ => return Err(usage("usage: edit ID [TEXT]")),
+            };
+            let text = match text {
+                Some(text) => text,
+                None => {
+                    let mut text = String::new();
+                    stdin
+                        .read_to_string(&mut text)
+                        .map_err(|e| CliError::Store(e.into()))?;
+                    text
+                }
+            };
+            change(&|post, actor| post.edit(actor, &text))
+        }
+        "submit" => {
+            expect(&["ID"])?;
+            change(&|post, actor| post.request_review(actor))
+        }
+        "approve" => {
+            expect(&["ID"])?;
+            change(&|post, actor| post.approve(actor))
+        }
+        "reject" => {
+            expect(&["ID", "REASON"])?;
+            change(&|post, actor| post.reject(actor, &rest[1]))
+        }
+        "schedule" => {
+            expect(&["ID", "TIME"])?;
+            let at = parse_time(&rest[1], clock.now())?;
+            change(&|post, actor| post.schedule(actor, at))
+        }
+        "publish-due" => {
+            expect(&[])?;
+            let mut out = String::new();
+            for id in store.ids()? {
+                let mut post = store.load(&id)?;
+                if post.status() != Status::Scheduled {
+                    continue;
+                }
+                post.publish_due(clock);
+                if post.status() == Status::Published {
+                    store.save(&id, &post)?;
+                    out += &format!("{}: {}\n", id, Status::Published);
+                }
+            }
+            Ok(out)
+        }
+        "list" => {
+            expect(&[])?;
+            let state = match &args.state {
+                Some(s) => Some(parse_status(s).map_err(usage)?),
+                None => None,
+            };
+            let mut rows = vec![];
+            for id in store.ids()? {
+                let post = store.load(&id)?;
+                if state.is_none_or(|s| s == post.status()) {
+                    rows.push((id, status_name(post.status()), post.author().name.clone()));
+                }
+            }
+            let width = rows.iter().map(|r| r.0.len()).max().unwrap_or(0);
+            Ok(rows
+                .iter()
+                .map(|(id, state, author)| format!("{:<width$}  {:<14}  {}\n", id, state, author))
+                .collect())
+        }
+        "show" => {
+            expect(&["ID"])?;
+            let id = &rest[0];
+            let post = store.load(id)?;
+            if args.html {
+                if post.status() != Status::Published {
+                    return Err(CliError::NotPublished(id.clone()));
+                }
+                return Ok(post.render_html_with(&HtmlOptions::default()));
+            }
+            Ok(show(id, &post))
+        }
+        "log" => {
+            expect(&["ID"])?;
+            let post = store.load(&rest[0])?;
+            Ok(post
+                .log()
+                .iter()
+                .map(|e| {
+                    let details = match &e.action {
+                        Action::Create(policy) => format!(" with {}", encode_policy(policy)),
+                        Action::Revert(n) => format!(" to revision {}", n),
+                        Action::Review(Verdict::Rejected(reason), _) => format!(": {}", reason),
+                        Action::Review(Verdict::Approved, Some(at)) => format!(" at {}", at),
+                        _ => String::new(),
+                    };
+                    format!(
+                        "{}  {}  {}{}  ({} -> {})\n",
+                        e.at,
+                        e.actor.name,
+                        e.action.name(),
+                        details,
+                        e.from,
+                        e.to
+                    )
+                })
+                .collect())
+        }
+        other => Err(usage(format!("unknown command \"{}\"", other))),
+    }
+}
+
+// the whole post, whatever its state: content() would hide the content of a draft
+fn show(id: &str, post: &Post) -> String {
+    let mut out = format!("id: {}\nstate: {}\n", id, post.status());
+    if let Some(at) = post.scheduled_at() {
+        out += &format!("scheduled at: {}\n", at);
+    }
+    out += &format!(
+        "author: {}\npolicy: {}\n",
+        post.author().name,
+        encode_policy(&post.policy)
+    );
+    if let Some(reason) = post.rejection() {
+        out += &format!("rejected: {}\n", reason);
+    }
+    for r in post.reviews() {
+        match &r.verdict {
+            Verdict::Approved => out += &format!("approved by {}\n", r.reviewer.name),
+            Verdict::Rejected(reason) => {
+                out += &format!("rejected by {}: {}\n", r.reviewer.name, reason)
+            }
+        }
+    }
+    let revisions = post.revisions();
+    let latest = revisions.last().map_or("", |r| r.content.as_str());
+    out += &format!("revision: {}\n\n{}", revisions.len() - 1, latest);
+    if !latest.is_empty() && !latest.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::FixedClock;
+
+    struct Blog {
+        dir: PathBuf,
+    }
+
+    impl Blog {
+        fn new(name: &str) -> Blog {
+            let dir =
+                env::temp_dir().join(format!("state-pattern-cli-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Blog { dir }
+        }
+
+        fn run(&self, user: &str, args: &str) -> Result<String, CliError> {
+            self.run_at(user, args, 1_000)
+        }
+
+        fn run_at(&self, user: &str, args: &str, now: Timestamp) -> Result<String, CliError> {
+            let mut all = vec![String::from("--dir"), self.dir.display().to_string()];
+            all.extend([String::from("--as"), user.to_string()]);
+            all.extend(args.split(' ').map(String::from));
+            run(&all, &mut "from stdin".as_bytes(), &FixedClock(now))
+        }
+    }
+
+    // the directory goes even when the test fails
+    impl Drop for Blog {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let blog = Blog::new("commands");
+        let (ana, bob, pat) = ("ana:author", "bob:reviewer", "pat:publisher");
+        assert_eq!("notam: draft\n", blog.run(ana, "new notam").unwrap());
+        assert!(matches!(
+            blog.run(ana, "new notam"),
+            Err(CliError::Exists(_))
+        ));
+        blog.run(ana, "edit notam runway").unwrap();
+        assert_eq!(
+            "notam: can't approve: the post is draft",
+            blog.run(bob, "approve notam").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "notam: bob can't edit: only its author can change a post",
+            blog.run(bob, "edit notam -").unwrap_err().to_string()
+        );
+        blog.run(ana, "submit notam").unwrap();
+        blog.run(bob, "reject notam closed?").unwrap();
+        blog.run(ana, "edit notam").unwrap();
+        blog.run(ana, "submit notam").unwrap();
+        assert_eq!(
+            "notam: scheduled\n",
+            blog.run(pat, "schedule notam +1h").unwrap()
+        );
+
+        blog.run(ana, "new other").unwrap();
+        assert_eq!(
+            "notam  scheduled       ana\nother  draft           ana\n",
+            blog.run(ana, "list").unwrap()
+        );
+        assert_eq!(
+            "other  draft           ana\n",
+            blog.run(ana, "list --state draft").unwrap()
+        );
+
+        assert_eq!("", blog.run_at(ana, "publish-due", 4_599).unwrap());
+        assert!(matches!(
+            blog.run(ana, "show notam --html"),
+            Err(CliError::NotPublished(_))
+        ));
+        assert_eq!(
+            "notam: published\n",
+            blog.run_at(ana, "publish-due", 4_600).unwrap()
+        );
+        assert_eq!(
+            "<p>from stdin</p>\n",
+            blog.run(ana, "show notam --html").unwrap()
+        );
+        let shown = blog.run(ana, "show notam").unwrap();
+        assert!(shown.starts_with("id: notam\nstate: published\n"));
+        assert!(shown.ends_with("revision: 2\n\nfrom stdin\n"));
+
+        let log = blog.run(ana, "log notam").unwrap();
+        let actions: Vec<&str> = log.lines().map(|l| l.split("  ").nth(2).unwrap()).collect();
+        assert_eq!(
+            vec![
+                "create with veto(approvals(1))",
+                "edit",
+                "request a review",
+                "reject: closed?",
+                "edit",
+                "request a review",
+                "schedule at 4600",
+                "publish",
+            ],
+            actions
+        );
+    }
+
+    #[test]
+    fn usage_errors() {
+        let blog = Blog::new("usage");
+        for args in [
+            "",
+            "new",
+            "fly notam",
+            "list --nope",
+            "new x --policy most(2)",
+        ] {
+            assert!(
+                matches!(blog.run("ana:author", args), Err(CliError::Usage(_))),
+                "{}",
+                args
+            );
+        }
+        assert!(matches!(
+            blog.run("ana:boss", "new x"),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            blog.run("ana:author", "approve nothing"),
+            Err(CliError::Store(StoreError::NotFound(_)))
+        ));
+        assert_eq!(
+            Ok(1_090),
+            parse_time("+90s", 1_000).map_err(|e| e.to_string())
+        );
+        // too far in the future for a Timestamp
+        for time in ["+18446744073709551615d", "+1s"] {
+            assert!(matches!(
+                parse_time(time, Timestamp::MAX),
+                Err(CliError::Usage(_))
+            ));
+        }
+    }
+}
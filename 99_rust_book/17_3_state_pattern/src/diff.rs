@@ -0,0 +1,239 @@
+/*
+ * Line diffs, with Myers' algorithm ("An O(ND) Difference Algorithm and Its Variations", 1986).
+ *
+ * Turning "a" into "b" is a walk on a grid, from the top left corner (nothing of either read yet)
+ * to the bottom right one: a step right deletes a line of "a", a step down inserts a line of "b",
+ * and where the two lines are equal a diagonal step keeps it for free. The shortest edit script is
+ * the path with the fewest right and down steps.
+ *
+ * Myers looks for it one number of edits "d" at a time: v[k] is how far right the best path with
+ * d edits got on diagonal k (x - y = k), and the diagonals with d + 1 edits are reached from the
+ * ones with d, by one step and then as many free diagonal steps as possible. The first d that
+ * reaches the corner is the answer, and keeping the diagonals -d..=d of v that round d wrote is
+ * enough to walk the path back. That's O((N + M) * D) time and O(D²) memory, which is little when
+ * the two texts are close, as revisions are.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    // the indexes of the line in "a" and in "b"
+    Equal(usize, usize),
+    // a line of "a" that isn't in "b"
+    Delete(usize),
+    // a line of "b" that isn't in "a"
+    Insert(usize),
+}
+
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    // k goes from -max to max, and v[k - 1] and v[k + 1] are read
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = vec![];
+
+    // whether the path to diagonal k with d edits comes from k + 1 (a step down, an insertion),
+    // "v" giving how far the paths with d - 1 edits got on a diagonal
+    let down =
+        |d: isize, k: isize, v: &dyn Fn(isize) -> isize| k == -d || (k != d && v(k - 1) < v(k + 1));
+
+    for d in 0..=max as isize {
+        let mut reached = false;
+        for k in (-d..=d).step_by(2) {
+            let mut x = if down(d, k, &|k| v[(k + offset) as usize]) {
+                v[(k + 1 + offset) as usize]
+            } else {
+                v[(k - 1 + offset) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                reached = true;
+                break;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        if reached {
+            break;
+        }
+    }
+
+    // back from the corner, one edit at a time: trace[d] holds the diagonals -d..=d after round d
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len() as isize).rev() {
+        let k = x - y;
+        // the paths with no edit all start from (0, 0)
+        let (prev_k, prev_x) = match d {
+            0 => (0, 0),
+            _ => {
+                let prev = &trace[d as usize - 1];
+                let at = |k: isize| prev[(k + d - 1) as usize];
+                let prev_k = if down(d, k, &at) { k + 1 } else { k - 1 };
+                (prev_k, at(prev_k))
+            }
+        };
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                edits.push(Edit::Insert(y as usize));
+            } else {
+                x -= 1;
+                edits.push(Edit::Delete(x as usize));
+            }
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+/*
+ * The unified format, as "diff -u" prints it: only the changed lines, with "context" unchanged lines
+ * around them, grouped into hunks. Each hunk starts with the line numbers it covers in both texts:
+ * "@@ -3,4 +3,5 @@" is 4 lines of "a" starting from its line 3, which became 5 lines of "b".
+ */
+pub fn unified(a_name: &str, b_name: &str, a: &str, b: &str, context: usize) -> String {
+    let (a, b): (Vec<&str>, Vec<&str>) = (a.lines().collect(), b.lines().collect());
+    let edits = diff(&a, &b);
+    let mut out = String::new();
+    if edits.iter().all(|e| matches!(e, Edit::Equal(..))) {
+        return out;
+    }
+    out += &format!("--- {}\n+++ {}\n", a_name, b_name);
+
+    // where each edit starts in "a" and "b"
+    let mut starts = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for e in &edits {
+        starts.push((i, j));
+        match e {
+            Edit::Equal(..) => (i, j) = (i + 1, j + 1),
+            Edit::Delete(_) => i += 1,
+            Edit::Insert(_) => j += 1,
+        }
+    }
+    starts.push((i, j));
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&i| !matches!(edits[i], Edit::Equal(..)))
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &c in &changes {
+        let (start, end) = (
+            c.saturating_sub(context),
+            (c + context + 1).min(edits.len()),
+        );
+        match hunks.last_mut() {
+            // close enough to the previous hunk to share its context
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let (a_start, b_start) = starts[start];
+        let (a_end, b_end) = starts[end];
+        // an empty range is numbered after the line before it
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{},0", start),
+            _ => format!("{},{}", start + 1, len),
+        };
+        out += &format!(
+            "@@ -{} +{} @@\n",
+            range(a_start, a_end - a_start),
+            range(b_start, b_end - b_start)
+        );
+        for e in &edits[start..end] {
+            match *e {
+                Edit::Equal(i, _) => out += &format!(" {}\n", a[i]),
+                Edit::Delete(i) => out += &format!("-{}\n", a[i]),
+                Edit::Insert(j) => out += &format!("+{}\n", b[j]),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shortest_script() {
+        // the example of the paper
+        let a: Vec<char> = "ABCABBA".chars().collect();
+        let b: Vec<char> = "CBABAC".chars().collect();
+        let edits = diff(&a, &b);
+        let changes = edits
+            .iter()
+            .filter(|e| !matches!(e, Edit::Equal(..)))
+            .count();
+        assert_eq!(5, changes);
+        // and it does turn a into b
+        let mut out = vec![];
+        for e in &edits {
+            match *e {
+                Edit::Equal(i, _) => out.push(a[i]),
+                Edit::Insert(j) => out.push(b[j]),
+                Edit::Delete(_) => {}
+            }
+        }
+        assert_eq!(b, out);
+
+        assert_eq!(vec![Edit::Insert(0)], diff(&[], &["x"]));
+        assert_eq!(vec![Edit::Delete(0)], diff(&["x"], &[]));
+        assert!(diff::<&str>(&[], &[]).is_empty());
+
+        // as short as the longest common subsequence says, for all the pairs of a few texts
+        let texts = [
+            "", "a", "ab", "ba", "abcab", "cbabac", "aaaa", "xaybzc", "abcabba",
+        ];
+        for a in texts.map(|t| t.chars().collect::<Vec<_>>()) {
+            for b in texts.map(|t| t.chars().collect::<Vec<_>>()) {
+                let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+                for i in (0..a.len()).rev() {
+                    for j in (0..b.len()).rev() {
+                        lcs[i][j] = match a[i] == b[j] {
+                            true => lcs[i + 1][j + 1] + 1,
+                            false => lcs[i + 1][j].max(lcs[i][j + 1]),
+                        };
+                    }
+                }
+                let equal = diff(&a, &b)
+                    .iter()
+                    .filter(|e| matches!(e, Edit::Equal(..)))
+                    .count();
+                assert_eq!(lcs[0][0], equal);
+            }
+        }
+    }
+
+    #[test]
+    fn unified_format() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let b = "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            "--- a\n+++ b\n\
+             @@ -1,3 +1,3 @@\n 1\n-2\n+two\n 3\n\
+             @@ -10,1 +10,2 @@\n 10\n+11\n",
+            unified("a", "b", a, b, 1)
+        );
+        // with more context the two hunks become one
+        assert!(unified("a", "b", a, b, 4).starts_with("--- a\n+++ b\n@@ -1,10 +1,11 @@\n"));
+        assert_eq!(
+            "--- a\n+++ b\n@@ -0,0 +1,1 @@\n+new\n",
+            unified("a", "b", "", "new", 3)
+        );
+        assert_eq!("", unified("a", "b", a, a, 3));
+    }
+}
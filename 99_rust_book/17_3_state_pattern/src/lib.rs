@@ -1,18 +1,200 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub mod audit;
+pub mod cli;
+pub mod diff;
+pub mod markdown;
+pub mod repository;
+pub mod review;
+pub mod revision;
+pub mod roles;
+pub mod store;
+
+pub use audit::{Action, Event, SCHEDULER};
+pub use markdown::HtmlOptions;
+pub use repository::{PostRepository, RepositoryError, Version};
+pub use review::{Decision, Policy, Review, Verdict};
+pub use revision::Revision;
+pub use roles::{Role, User};
+pub use store::{PostStore, StoreError, Stored};
+
+// seconds since the Unix epoch
+pub type Timestamp = u64;
+
+// scheduled posts are published once the clock says it's time: I pass the clock around instead of
+// reading the system time directly, so a test can pretend it's any time it wants.
+// Send + Sync as the posts of a PostRepository are shared between threads, and their clock too
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    }
+}
+
+// a clock stuck at the given time
+pub struct FixedClock(pub Timestamp);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Timestamp {
+        self.0
+    }
+}
+
 pub struct Post {
     state: Option<Box<dyn State>>,
     content: String,
+    policy: Policy,
+    author: User,
+    clock: Arc<dyn Clock>,
+    // every change, in order: see audit.rs
+    log: Vec<Event>,
+}
+
+// the states, as something that can be shown, compared and stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Draft,
+    PendingReview,
+    Scheduled,
+    Published,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Status::Draft => "draft",
+            Status::PendingReview => "pending review",
+            Status::Scheduled => "scheduled",
+            Status::Published => "published",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PostError {
+    // the actor isn't allowed to do that, see roles.rs
+    PermissionDenied {
+        actor: String,
+        action: &'static str,
+        reason: &'static str,
+    },
+    // that can't be done in the state the post is in, e.g. approving a draft
+    InvalidTransition {
+        action: &'static str,
+        status: Status,
+    },
+    // reverting to a revision that doesn't exist (yet)
+    UnknownRevision {
+        revision: usize,
+        latest: usize,
+    },
+    // every reviewer gets one say per review round
+    DuplicateReview {
+        reviewer: String,
+    },
+    // Post::replay was given events that don't follow each other
+    InvalidLog {
+        event: usize,
+        reason: String,
+    },
 }
 
+impl fmt::Display for PostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PostError::PermissionDenied {
+                actor,
+                action,
+                reason,
+            } => write!(f, "{} can't {}: {}", actor, action, reason),
+            PostError::InvalidTransition { action, status } => {
+                write!(f, "can't {}: the post is {}", action, status)
+            }
+            PostError::UnknownRevision { revision, latest } => write!(
+                f,
+                "there is no revision {}: the latest one is {}",
+                revision, latest
+            ),
+            PostError::DuplicateReview { reviewer } => {
+                write!(f, "{} has already reviewed this post", reviewer)
+            }
+            PostError::InvalidLog { event, reason } => {
+                write!(f, "invalid log, event {}: {}", event, reason)
+            }
+        }
+    }
+}
+
+impl Error for PostError {}
+
 impl Post {
-    pub fn new() -> Post {
+    pub fn new(author: &User) -> Post {
+        Post::with_policy(author, Policy::default())
+    }
+
+    // the policy decides how many reviews it takes to publish the post, see review.rs
+    pub fn with_policy(author: &User, policy: Policy) -> Post {
+        Post::with_clock(author, policy, Arc::new(SystemClock))
+    }
+
+    // the clock gives the time of the events in the log
+    pub fn with_clock(author: &User, policy: Policy, clock: Arc<dyn Clock>) -> Post {
+        let created = Event {
+            at: clock.now(),
+            actor: author.clone(),
+            from: Status::Draft,
+            to: Status::Draft,
+            action: Action::Create(policy.clone()),
+        };
         Post {
-            state: Some(Box::new(Draft {})),
+            state: Some(Box::new(Draft { rejection: None })),
             content: String::new(),
+            policy,
+            author: author.clone(),
+            clock,
+            log: vec![created],
         }
     }
 
-    pub fn add_text(&mut self, text: &str) {
-        self.content.push_str(text);
+    pub fn author(&self) -> &User {
+        &self.author
+    }
+
+    pub fn status(&self) -> Status {
+        self.state.as_ref().unwrap().status()
+    }
+
+    // what happened to the post since it was created, oldest first
+    pub fn log(&self) -> &[Event] {
+        &self.log
+    }
+
+    // only while the post is a draft
+    pub fn add_text(&mut self, actor: &User, text: &str) -> Result<(), PostError> {
+        self.act(actor, Action::AddText(text.to_string()))
+    }
+
+    // replaces the whole content, only while the post is a draft
+    pub fn edit(&mut self, actor: &User, text: &str) -> Result<(), PostError> {
+        self.act(actor, Action::Edit(text.to_string()))
+    }
+
+    // brings back the content of an earlier revision, as a new revision
+    pub fn revert(&mut self, actor: &User, revision: usize) -> Result<(), PostError> {
+        self.act(actor, Action::Revert(revision))
     }
 
     pub fn content(&self) -> &str {
@@ -21,61 +203,314 @@ impl Post {
         self.state.as_ref().unwrap().content(self)
     }
 
-    pub fn request_review(&mut self) {
+    // why the last review sent the post back to draft, if it did
+    pub fn rejection(&self) -> Option<&str> {
+        self.state.as_ref().unwrap().rejection()
+    }
+
+    // when a scheduled post is going to be published, or will be once the policy agrees
+    pub fn scheduled_at(&self) -> Option<Timestamp> {
+        self.state.as_ref().unwrap().scheduled_at()
+    }
+
+    // the reviews given so far, while the post is pending
+    pub fn reviews(&self) -> &[Review] {
+        self.state.as_ref().unwrap().reviews()
+    }
+
+    pub fn request_review(&mut self, actor: &User) -> Result<(), PostError> {
+        self.act(actor, Action::RequestReview)
+    }
+
+    pub fn approve(&mut self, reviewer: &User) -> Result<(), PostError> {
+        self.act(reviewer, Action::Review(Verdict::Approved, None))
+    }
+
+    // once the policy agrees, the post goes back to draft: the reason is kept until the next review
+    pub fn reject(&mut self, reviewer: &User, reason: &str) -> Result<(), PostError> {
+        self.act(
+            reviewer,
+            Action::Review(Verdict::Rejected(reason.to_string()), None),
+        )
+    }
+
+    // an approval that only publishes the post at the given time, once the policy agrees
+    pub fn schedule(&mut self, reviewer: &User, at: Timestamp) -> Result<(), PostError> {
+        self.act(reviewer, Action::Review(Verdict::Approved, Some(at)))
+    }
+
+    // publishes a scheduled post if the clock says its time has come, and does nothing otherwise:
+    // it is meant to be called every so often on every post
+    pub fn publish_due(&mut self, clock: &dyn Clock) {
+        let scheduler = User::new(SCHEDULER, &[]);
+        self.apply(&scheduler, clock.now(), Action::PublishDue);
+    }
+
+    fn act(&mut self, actor: &User, action: Action) -> Result<(), PostError> {
+        self.check(actor, &action)?;
+        self.apply(actor, self.clock.now(), action);
+        Ok(())
+    }
+
+    // whether the actor can do that: the live calls and the replay go through the same checks
+    fn check(&self, actor: &User, action: &Action) -> Result<(), PostError> {
+        roles::permit(actor, &self.author, action)?;
+        if !self.state.as_ref().unwrap().allows(action) {
+            return Err(PostError::InvalidTransition {
+                action: action.name(),
+                status: self.status(),
+            });
+        }
+        if let Action::Revert(revision) = *action {
+            let latest = self.latest_revision();
+            if revision > latest {
+                return Err(PostError::UnknownRevision { revision, latest });
+            }
+        }
+        if let Action::Review(..) = action {
+            if self.reviews().iter().any(|r| r.reviewer.name == actor.name) {
+                return Err(PostError::DuplicateReview {
+                    reviewer: actor.name.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // does it, and logs it if something changed: a publish_due too early isn't logged
+    fn apply(&mut self, actor: &User, at: Timestamp, action: Action) {
+        let from = self.status();
+        let before = (self.reviews().len(), self.scheduled_at());
+        match &action {
+            // only with_clock creates a post
+            Action::Create(_) => return,
+            Action::AddText(text) => self.content.push_str(text),
+            Action::Edit(text) => self.content = text.clone(),
+            Action::Revert(revision) => {
+                if let Some(old) = self.revisions().into_iter().nth(*revision) {
+                    self.content = old.content;
+                }
+            }
+            Action::RequestReview => self.transition(|state| state.request_review()),
+            Action::Review(verdict, when) => {
+                let review = Review {
+                    reviewer: actor.clone(),
+                    verdict: verdict.clone(),
+                };
+                // not through "transition": the closure would borrow the whole of self, policy included
+                if let Some(state) = self.state.take() {
+                    self.state = Some(state.review(review, *when, &self.policy));
+                }
+            }
+            Action::PublishDue => self.transition(|state| state.publish_due(at)),
+        }
+        let to = self.status();
+        let changed = from != to || before != (self.reviews().len(), self.scheduled_at());
+        // a change of content is a new revision, even when the state stays the same
+        if changed || action.changes_content() {
+            self.log.push(Event {
+                at,
+                actor: actor.clone(),
+                from,
+                to,
+                action,
+            });
+        }
+    }
+
+    // every transition has the same shape, so the closure says which one to call
+    fn transition(&mut self, f: impl FnOnce(Box<dyn State>) -> Box<dyn State>) {
         // as Rust doesn't allow unpopulated struct fields and I want to move
         // state out of post, I had to declare state as an Option. In this way
         // I am able to call "take", which moves the value out of state, leaving
         // "None" in its place
         if let Some(state) = self.state.take() {
-            self.state = Some(state.request_review());
-        }
-    }
-
-    pub fn approve(&mut self) {
-        if let Some(state) = self.state.take() {
-            self.state = Some(state.approve());
+            self.state = Some(f(state));
         }
     }
 }
 
-trait State {
+// Send + Sync for the same reason as Clock
+trait State: Send + Sync {
+    fn status(&self) -> Status;
+    // the actions that can be done in this state: the others are refused before reaching the
+    // transitions below, which are left with nothing to do for them
+    fn allows(&self, action: &Action) -> bool;
     fn request_review(self: Box<Self>) -> Box<dyn State>;
-    fn approve(self: Box<Self>) -> Box<dyn State>;
+    // an approval or a rejection, "at" is the publication time of a scheduled approval.
+    // Most states ignore it, but each of them has to say so: a default "self" wouldn't
+    // compile, as turning Box<Self> into Box<dyn State> needs Self to be Sized
+    fn review(
+        self: Box<Self>,
+        review: Review,
+        at: Option<Timestamp>,
+        policy: &Policy,
+    ) -> Box<dyn State>;
+    fn publish_due(self: Box<Self>, _now: Timestamp) -> Box<dyn State>;
     // content needs to specify a lifetime parameter as it has two borrowed parameters
-    fn content<'a>(&self, post: &'a Post) -> &'a str {
+    fn content<'a>(&self, _post: &'a Post) -> &'a str {
         ""
     }
+    fn rejection(&self) -> Option<&str> {
+        None
+    }
+    fn scheduled_at(&self) -> Option<Timestamp> {
+        None
+    }
+    fn reviews(&self) -> &[Review] {
+        &[]
+    }
 }
 
-struct Draft {}
+struct Draft {
+    rejection: Option<String>,
+}
 
 impl State for Draft {
+    fn status(&self) -> Status {
+        Status::Draft
+    }
+    fn allows(&self, action: &Action) -> bool {
+        matches!(
+            action,
+            Action::AddText(_) | Action::Edit(_) | Action::Revert(_) | Action::RequestReview
+        )
+    }
     fn request_review(self: Box<Self>) -> Box<dyn State> {
-        Box::new(PendingReview {}) // requesting a review on a draft state -> invalidating the whole state
+        // requesting a review on a draft state -> invalidating the whole state
+        Box::new(PendingReview {
+            reviews: vec![],
+            at: None,
+        })
+    }
+    fn review(self: Box<Self>, _: Review, _: Option<Timestamp>, _: &Policy) -> Box<dyn State> {
+        self
     }
-    fn approve(self: Box<Self>) -> Box<dyn State> {
+    fn publish_due(self: Box<Self>, _now: Timestamp) -> Box<dyn State> {
         self
     }
+    fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
 }
 
-struct PendingReview {}
+struct PendingReview {
+    reviews: Vec<Review>,
+    // asked by a scheduled approval
+    at: Option<Timestamp>,
+}
 
 impl State for PendingReview {
+    fn status(&self) -> Status {
+        Status::PendingReview
+    }
+    fn allows(&self, action: &Action) -> bool {
+        matches!(action, Action::Review(..))
+    }
     fn request_review(self: Box<Self>) -> Box<dyn State> {
         self // no need to do anything as the post is already in the pending review state
     }
-    fn approve(self: Box<Self>) -> Box<dyn State> {
-        Box::new(Published {})
+    // here is the coupling the comment below talks about: PendingReview has to know about Scheduled
+    fn review(
+        mut self: Box<Self>,
+        review: Review,
+        at: Option<Timestamp>,
+        policy: &Policy,
+    ) -> Box<dyn State> {
+        // the last scheduled approval sets the time
+        self.at = at.or(self.at);
+        self.reviews.push(review);
+        match policy.decide(&self.reviews) {
+            Decision::Pending => self,
+            Decision::Approved => match self.at {
+                Some(at) => Box::new(Scheduled { at }),
+                None => Box::new(Published {}),
+            },
+            // the rejection that made the policy decide
+            Decision::Rejected => Box::new(Draft {
+                rejection: self.reviews.pop().and_then(|r| match r.verdict {
+                    Verdict::Rejected(reason) => Some(reason),
+                    Verdict::Approved => None,
+                }),
+            }),
+        }
+    }
+    fn publish_due(self: Box<Self>, _now: Timestamp) -> Box<dyn State> {
+        self
+    }
+    fn scheduled_at(&self) -> Option<Timestamp> {
+        self.at
+    }
+    fn reviews(&self) -> &[Review] {
+        &self.reviews
+    }
+}
+
+// approved, but not visible before "at"
+struct Scheduled {
+    at: Timestamp,
+}
+
+impl State for Scheduled {
+    fn status(&self) -> Status {
+        Status::Scheduled
+    }
+    // it has already been approved, but it can be pulled back or moved
+    fn allows(&self, action: &Action) -> bool {
+        matches!(
+            action,
+            Action::Review(Verdict::Rejected(_), _)
+                | Action::Review(Verdict::Approved, Some(_))
+                | Action::PublishDue
+        )
+    }
+    fn request_review(self: Box<Self>) -> Box<dyn State> {
+        self
+    }
+    // the post can still be pulled back before it goes out, or moved to another time,
+    // by a single reviewer: the policy has already been satisfied
+    fn review(
+        self: Box<Self>,
+        review: Review,
+        at: Option<Timestamp>,
+        _: &Policy,
+    ) -> Box<dyn State> {
+        match (review.verdict, at) {
+            (Verdict::Rejected(reason), _) => Box::new(Draft {
+                rejection: Some(reason),
+            }),
+            (Verdict::Approved, Some(at)) => Box::new(Scheduled { at }),
+            (Verdict::Approved, None) => self,
+        }
+    }
+    fn publish_due(self: Box<Self>, now: Timestamp) -> Box<dyn State> {
+        if now >= self.at {
+            Box::new(Published {})
+        } else {
+            self
+        }
+    }
+    fn scheduled_at(&self) -> Option<Timestamp> {
+        Some(self.at)
     }
 }
 
 struct Published {}
 
 impl State for Published {
+    fn status(&self) -> Status {
+        Status::Published
+    }
+    fn allows(&self, _action: &Action) -> bool {
+        false
+    }
     fn request_review(self: Box<Self>) -> Box<dyn State> {
         self
     }
-    fn approve(self: Box<Self>) -> Box<dyn State> {
+    fn review(self: Box<Self>, _: Review, _: Option<Timestamp>, _: &Policy) -> Box<dyn State> {
+        self
+    }
+    fn publish_due(self: Box<Self>, _now: Timestamp) -> Box<dyn State> {
         self
     }
     fn content<'a>(&self, post: &'a Post) -> &'a str {
@@ -89,7 +524,7 @@ impl State for Published {
  * I'd have to change the code in "PendingReview" to transition to "Scheduled", before going to
  * "Published"
  * 2. code duplication: request_review and approve in Post are very similar. To get rid of that
- * duplication I'd have to use macros in Rust
+ * duplication I'd have to use macros in Rust (or, as "transition" does, pass the call in a closure)
  *
  * Let's see another approach to implement the State Design Pattern
 */
@@ -101,13 +536,16 @@ pub struct EncodedPost {
 
 pub struct EncodedDraft {
     content: String,
+    rejection: Option<String>,
 }
 
 impl EncodedPost {
-    // the only way to create a draft is to call new on EncodedPost
+    // the only way to create a draft is to call new on EncodedPost: that's why new doesn't return Self
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> EncodedDraft {
         EncodedDraft {
             content: String::new(),
+            rejection: None,
         }
     }
     pub fn content(&self) -> &str {
@@ -121,9 +559,13 @@ impl EncodedDraft {
     pub fn add_text(&mut self, text: &str) {
         self.content.push_str(text)
     }
+    pub fn rejection(&self) -> Option<&str> {
+        self.rejection.as_deref()
+    }
     // I cannot approve a draft, nor print its content, I can only request a review
     // this method takes ownership of self, so it invalidates the previous state
     pub fn request_review(self) -> EncodedPendingReview {
+        // the reason of the previous rejection is dropped here
         EncodedPendingReview {
             content: self.content,
         }
@@ -141,6 +583,45 @@ impl EncodedPendingReview {
             content: self.content,
         }
     }
+    pub fn reject(self, reason: &str) -> EncodedDraft {
+        EncodedDraft {
+            content: self.content,
+            rejection: Some(reason.to_string()),
+        }
+    }
+    pub fn schedule(self, at: Timestamp) -> EncodedScheduled {
+        EncodedScheduled {
+            content: self.content,
+            at,
+        }
+    }
+}
+
+pub struct EncodedScheduled {
+    content: String,
+    at: Timestamp,
+}
+
+impl EncodedScheduled {
+    pub fn at(&self) -> Timestamp {
+        self.at
+    }
+    pub fn reject(self, reason: &str) -> EncodedDraft {
+        EncodedDraft {
+            content: self.content,
+            rejection: Some(reason.to_string()),
+        }
+    }
+    // a transition that can fail: when it's too early, I get the scheduled post back in the Err
+    pub fn publish(self, clock: &dyn Clock) -> Result<EncodedPost, EncodedScheduled> {
+        if clock.now() >= self.at {
+            Ok(EncodedPost {
+                content: self.content,
+            })
+        } else {
+            Err(self)
+        }
+    }
 }
 
 /*
@@ -151,3 +632,129 @@ impl EncodedPendingReview {
  * such as ownership, which typical OOP languages don't have. We can still leverage the features
  * of Rust to implement some OOP patterns in a different way
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reject_and_schedule() {
+        let ana = User::new("ana", &[Role::Author]);
+        let pat = User::new("pat", &[Role::Publisher]);
+        let mut post = Post::new(&ana);
+        post.add_text(&ana, "NOTAM: runway 27 closed").unwrap();
+        post.request_review(&ana).unwrap();
+        post.reject(&pat, "which airport?").unwrap();
+        assert_eq!(Some("which airport?"), post.rejection());
+        assert_eq!(
+            Err(PostError::InvalidTransition {
+                action: "schedule",
+                status: Status::Draft
+            }),
+            post.schedule(&pat, 100)
+        );
+
+        post.add_text(&ana, " at LIRF").unwrap();
+        post.request_review(&ana).unwrap();
+        assert_eq!(None, post.rejection());
+        post.schedule(&pat, 100).unwrap();
+        assert_eq!(Some(100), post.scheduled_at());
+        assert!(post.add_text(&ana, "!").is_err());
+        post.publish_due(&FixedClock(99));
+        assert_eq!("", post.content());
+        post.publish_due(&FixedClock(100));
+        assert_eq!("NOTAM: runway 27 closed at LIRF", post.content());
+    }
+
+    #[test]
+    fn permissions() {
+        let ana = User::new("ana", &[Role::Author, Role::Reviewer]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let mut post = Post::new(&ana);
+        assert_eq!(
+            Err(PostError::PermissionDenied {
+                actor: String::from("bob"),
+                action: "add text",
+                reason: "only its author can change a post"
+            }),
+            post.add_text(&bob, "spam")
+        );
+        assert_eq!(
+            Err(PostError::InvalidTransition {
+                action: "approve",
+                status: Status::Draft
+            }),
+            post.approve(&bob)
+        );
+        post.request_review(&ana).unwrap();
+        let err = post.approve(&ana).unwrap_err();
+        assert_eq!(
+            "ana can't approve: nobody can approve their own post",
+            err.to_string()
+        );
+        // a refused action leaves no trace
+        assert_eq!(2, post.log().len());
+        post.approve(&bob).unwrap();
+        assert_eq!(Status::Published, post.status());
+    }
+
+    #[test]
+    fn many_reviewers() {
+        let ana = User::new("ana", &[Role::Author]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let eve = User::new("eve", &[Role::Reviewer]);
+        let sam = User::new("sam", &[Role::Publisher]);
+        let policy = Policy::Veto(Box::new(Policy::All(vec![
+            Policy::Approvals(2),
+            Policy::Role(Role::Publisher),
+        ])));
+        let mut post = Post::with_policy(&ana, policy);
+        post.add_text(&ana, "bulletin").unwrap();
+        post.request_review(&ana).unwrap();
+
+        post.approve(&bob).unwrap();
+        assert_eq!(
+            Err(PostError::DuplicateReview {
+                reviewer: String::from("bob")
+            }),
+            post.approve(&bob)
+        );
+        post.approve(&eve).unwrap();
+        assert_eq!("", post.content());
+        assert_eq!(vec!["bob", "eve"], names(post.reviews()));
+        post.approve(&sam).unwrap();
+        assert_eq!("bulletin", post.content());
+
+        // a new round starts from scratch, and a single veto is enough
+        let mut post = Post::with_policy(&ana, Policy::Veto(Box::new(Policy::Approvals(2))));
+        post.request_review(&ana).unwrap();
+        post.approve(&bob).unwrap();
+        post.reject(&eve, "wrong runway").unwrap();
+        assert_eq!(Some("wrong runway"), post.rejection());
+        post.request_review(&ana).unwrap();
+        assert!(post.reviews().is_empty());
+        post.approve(&bob).unwrap();
+    }
+
+    fn names(reviews: &[Review]) -> Vec<&str> {
+        reviews.iter().map(|r| r.reviewer.name.as_str()).collect()
+    }
+
+    #[test]
+    fn encoded_reject_and_schedule() {
+        let mut draft = EncodedPost::new();
+        draft.add_text("NOTAM");
+        let mut draft = draft.request_review().reject("too short");
+        assert_eq!(Some("too short"), draft.rejection());
+        draft.add_text(": runway 27 closed");
+
+        let scheduled = draft.request_review().schedule(100);
+        let scheduled = match scheduled.publish(&FixedClock(50)) {
+            Ok(_) => panic!("published too early"),
+            Err(s) => s,
+        };
+        assert_eq!(100, scheduled.at());
+        let post = scheduled.publish(&FixedClock(150)).ok().unwrap();
+        assert_eq!("NOTAM: runway 27 closed", post.content());
+    }
+}
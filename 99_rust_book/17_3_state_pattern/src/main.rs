@@ -1,17 +1,22 @@
-use state_pattern::{EncodedPost, Post};
+use std::{env, io, process};
 
-fn main() {
-    let mut post = Post::new();
-    post.add_text("new text");
-    assert_eq!("", post.content());
-    post.request_review();
-    assert_eq!("", post.content());
-    post.approve();
-    assert_eq!("new text", post.content());
+use state_pattern::{
+    cli::{self, CliError},
+    SystemClock,
+};
 
-    let mut encoded = EncodedPost::new();
-    encoded.add_text("encoded");
-    let encoded = encoded.request_review();
-    let encoded = encoded.approve();
-    assert_eq!("encoded", encoded.content());
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match cli::run(&args, &mut io::stdin(), &SystemClock) {
+        Ok(out) => print!("{}", out),
+        Err(e @ CliError::Usage(_)) => {
+            eprintln!("{}", e);
+            eprintln!("run with --help to see the available commands");
+            process::exit(2)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1)
+        }
+    }
 }
@@ -0,0 +1,869 @@
+use std::collections::HashMap;
+
+use crate::{EncodedPost, Post};
+
+/*
+ * The content of a post is written in a subset of CommonMark (https://spec.commonmark.org), and
+ * rendered to HTML when it is published:
+ * - "#" to "######" headings
+ * - paragraphs, with "*emphasis*", "**strong emphasis**" (or with "_"), `code` and
+ *   "[links](https://example.com "with a title")", "<https://autolinks>" too
+ * - "-", "+", "*" and "1." (or "1)") lists, which can hold any other block, nested lists included
+ * - "> block quotes", the same
+ * - fenced (``` or ~~~) and indented code blocks
+ *
+ * The rest (setext headings, thematic breaks, tables, reference links, entities, HTML blocks...) is
+ * left out, and a list is loose (its items wrapped in <p>) as soon as it has a blank line inside.
+ *
+ * Rendering is done in two steps, like most Markdown parsers do: first the lines are grouped into
+ * blocks, which can contain other blocks, and then the text of each block is parsed for the inline
+ * elements, which can't span two blocks.
+ *
+ * The posts end up on the intranet, so any HTML in them is escaped unless HtmlOptions says
+ * otherwise, and only the links to web pages, mail addresses and relative ones are kept: the others,
+ * like "javascript:", could run code on click.
+ */
+
+#[derive(Debug, Clone, Default)]
+pub struct HtmlOptions {
+    // lets the inline HTML tags and the unsafe links through: only for content that is trusted
+    pub raw_html: bool,
+}
+
+impl Post {
+    // the content as HTML: empty until the post is published, as content() is
+    pub fn render_html(&self) -> String {
+        self.render_html_with(&HtmlOptions::default())
+    }
+
+    pub fn render_html_with(&self, options: &HtmlOptions) -> String {
+        to_html(self.content(), options)
+    }
+}
+
+impl EncodedPost {
+    // only a published post has one
+    pub fn render_html(&self) -> String {
+        to_html(self.content(), &HtmlOptions::default())
+    }
+}
+
+pub fn to_html(text: &str, options: &HtmlOptions) -> String {
+    let lines: Vec<String> = text.lines().map(expand_tabs).collect();
+    let mut out = String::new();
+    for block in parse(&lines) {
+        render(&block, false, options, &mut out);
+    }
+    out
+}
+
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    // with the language, from the info string of the fence
+    Code(Option<String>, String),
+    Quote(Vec<Block>),
+    List {
+        // the number of the first item, for the ordered ones
+        start: Option<u64>,
+        tight: bool,
+        items: Vec<Vec<Block>>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Marker {
+    Bullet(char),
+    // with the number and the delimiter, "." or ")"
+    Ordered(u64, char),
+}
+
+impl Marker {
+    // whether two items belong to the same list
+    fn same_list(self, other: Marker) -> bool {
+        match (self, other) {
+            (Marker::Bullet(a), Marker::Bullet(b)) => a == b,
+            (Marker::Ordered(_, a), Marker::Ordered(_, b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// the tabs in the indentation count as 4 spaces: that's all the tab handling there is
+fn expand_tabs(line: &str) -> String {
+    let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+    let spaces: usize = line[..indent]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    format!("{}{}", " ".repeat(spaces), &line[indent..])
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// only spaces and tabs: other whitespace, like the ideographic space, is text
+fn blank(line: &str) -> bool {
+    line.trim_matches(|c: char| c.is_ascii_whitespace())
+        .is_empty()
+}
+
+fn heading(t: &str) -> Option<(usize, &str)> {
+    let level = t.chars().take_while(|&c| c == '#').count();
+    let rest = &t[level..];
+    if level == 0 || level > 6 || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    let rest = rest.trim();
+    // the closing "#"s are optional, and need a space before them
+    let without = rest.trim_end_matches('#');
+    Some(match without {
+        "" => (level, ""),
+        w if w.ends_with(' ') => (level, w.trim_end()),
+        _ => (level, rest),
+    })
+}
+
+// the char, the length of the fence and the info string
+fn fence(t: &str) -> Option<(char, usize, &str)> {
+    let c = t.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let n = t.chars().take_while(|&x| x == c).count();
+    let info = t[n..].trim();
+    if n < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((c, n, info))
+}
+
+// the marker, and how many columns it takes with the spaces after it
+fn list_marker(t: &str) -> Option<(Marker, usize)> {
+    let digits = t.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (marker, len) = match t.chars().next()? {
+        c @ ('-' | '+' | '*') => (Marker::Bullet(c), 1),
+        _ if (1..=9).contains(&digits) => {
+            let delimiter = t[digits..]
+                .chars()
+                .next()
+                .filter(|&c| c == '.' || c == ')')?;
+            (
+                Marker::Ordered(t[..digits].parse().ok()?, delimiter),
+                digits + 1,
+            )
+        }
+        _ => return None,
+    };
+    let rest = &t[len..];
+    if blank(rest) {
+        return Some((marker, len + 1));
+    }
+    let spaces = indent(rest);
+    match spaces {
+        0 => None,
+        // more than that starts an indented code block inside the item
+        5.. => Some((marker, len + 1)),
+        _ => Some((marker, len + spaces)),
+    }
+}
+
+// whether the line ends a paragraph, by starting another block
+fn interrupts(line: &str) -> bool {
+    let i = indent(line);
+    let t = &line[i..];
+    i < 4
+        && (heading(t).is_some()
+            || fence(t).is_some()
+            || t.starts_with('>')
+            // only the lists that can't be mistaken for text, like "2016. was a good year"
+            || match list_marker(t) {
+                Some((Marker::Ordered(n, _), w)) => n == 1 && !blank(t.get(w..).unwrap_or("")),
+                Some((Marker::Bullet(_), w)) => !blank(t.get(w..).unwrap_or("")),
+                None => false,
+            })
+}
+
+fn parse(lines: &[String]) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        let ind = indent(line);
+        if blank(line) {
+            i += 1;
+            continue;
+        }
+        if ind >= 4 {
+            let mut code = vec![];
+            while i < lines.len() && (blank(&lines[i]) || indent(&lines[i]) >= 4) {
+                code.push(lines[i].get(4..).unwrap_or(""));
+                i += 1;
+            }
+            while code.last().is_some_and(|l| blank(l)) {
+                code.pop();
+            }
+            blocks.push(Block::Code(None, code.join("\n") + "\n"));
+            continue;
+        }
+
+        let t = &line[ind..];
+        if let Some((level, text)) = heading(t) {
+            blocks.push(Block::Heading(level, text.to_string()));
+            i += 1;
+        } else if let Some((c, n, info)) = fence(t) {
+            i += 1;
+            let mut code = String::new();
+            while i < lines.len() {
+                let l = &lines[i];
+                let li = indent(l);
+                i += 1;
+                // the closing fence is at least as long as the opening one
+                if li < 4 && fence(&l[li..]).is_some_and(|f| f.0 == c && f.1 >= n && f.2.is_empty())
+                {
+                    break;
+                }
+                // the content loses as much indentation as the fence has
+                code += &l[li.min(ind)..];
+                code.push('\n');
+            }
+            let language = info.split_whitespace().next().map(String::from);
+            blocks.push(Block::Code(language, code));
+        } else if t.starts_with('>') {
+            let mut inner: Vec<String> = vec![];
+            while i < lines.len() {
+                let l = &lines[i];
+                let li = indent(l);
+                if li < 4 && l[li..].starts_with('>') {
+                    let rest = &l[li + 1..];
+                    inner.push(rest.strip_prefix(' ').unwrap_or(rest).to_string());
+                } else if !blank(l) && inner.last().is_some_and(|p| !blank(p)) && !interrupts(l) {
+                    // a "lazy" line, that goes on with the paragraph without the ">"
+                    inner.push(l.trim_start().to_string());
+                } else {
+                    break;
+                }
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse(&inner)));
+        } else if list_marker(t).is_some() {
+            let (list, next) = parse_list(lines, i);
+            blocks.push(list);
+            i = next;
+        } else {
+            let mut text = vec![t];
+            i += 1;
+            while i < lines.len() && !blank(&lines[i]) && !interrupts(&lines[i]) {
+                text.push(lines[i].trim_start());
+                i += 1;
+            }
+            let text = text.join("\n");
+            let text = text.trim_end_matches(|c: char| c.is_ascii_whitespace());
+            blocks.push(Block::Paragraph(text.to_string()));
+        }
+    }
+    blocks
+}
+
+// the lines of every item lose the indentation of its content, and are parsed on their own
+fn parse_list(lines: &[String], mut i: usize) -> (Block, usize) {
+    let mut items: Vec<Vec<String>> = vec![];
+    let (mut first, mut content, mut loose) = (None, 0, false);
+    while i < lines.len() {
+        let l = &lines[i];
+        let li = indent(l);
+        let after_blank = items
+            .last()
+            .and_then(|item| item.last())
+            .is_some_and(|p| blank(p));
+        if blank(l) {
+            if let Some(item) = items.last_mut() {
+                item.push(String::new());
+            }
+        } else if !items.is_empty() && li >= content {
+            items.last_mut().unwrap().push(l[content..].to_string());
+        } else if let Some((marker, width)) = (li < 4)
+            .then(|| list_marker(&l[li..]))
+            .flatten()
+            .filter(|(m, _)| first.is_none_or(|f: Marker| f.same_list(*m)))
+        {
+            first = first.or(Some(marker));
+            loose |= after_blank;
+            content = li + width;
+            items.push(vec![l.get(content..).unwrap_or("").to_string()]);
+        } else if !after_blank && !interrupts(l) {
+            // a lazy line: the first line is always an item, so there is one
+            items.last_mut().unwrap().push(l.trim_start().to_string());
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    // the blank lines after the last item aren't part of the list
+    let mut items: Vec<Vec<String>> = items
+        .into_iter()
+        .map(|mut item| {
+            while item.len() > 1 && item.last().is_some_and(|l| blank(l)) {
+                item.pop();
+            }
+            item
+        })
+        .collect();
+    loose |= items.iter().any(|item| item.iter().any(|l| blank(l)));
+    let start = match first {
+        Some(Marker::Ordered(n, _)) => Some(n),
+        _ => None,
+    };
+    let items = items.iter_mut().map(|item| parse(item)).collect();
+    let list = Block::List {
+        start,
+        tight: !loose,
+        items,
+    };
+    (list, i)
+}
+
+// "tight" paragraphs (in the items of a tight list) aren't wrapped in <p>
+fn render(block: &Block, tight: bool, options: &HtmlOptions, out: &mut String) {
+    match block {
+        Block::Heading(level, text) => {
+            *out += &format!("<h{}>{}</h{}>\n", level, inline(text, options), level);
+        }
+        Block::Paragraph(text) if tight => *out += &inline(text, options),
+        Block::Paragraph(text) => *out += &format!("<p>{}</p>\n", inline(text, options)),
+        Block::Code(language, code) => {
+            *out += "<pre><code";
+            if let Some(language) = language {
+                *out += &format!(" class=\"language-{}\"", escape(language));
+            }
+            *out += &format!(">{}</code></pre>\n", escape(code));
+        }
+        Block::Quote(blocks) => {
+            *out += "<blockquote>\n";
+            for b in blocks {
+                render(b, false, options, out);
+            }
+            *out += "</blockquote>\n";
+        }
+        Block::List {
+            start,
+            tight,
+            items,
+        } => {
+            let tag = match start {
+                None => "ul",
+                Some(_) => "ol",
+            };
+            match start {
+                Some(n) if *n != 1 => *out += &format!("<ol start=\"{}\">\n", n),
+                _ => *out += &format!("<{}>\n", tag),
+            }
+            for item in items {
+                *out += "<li>";
+                for b in item {
+                    // the blocks go on their own line, but for the text of a tight item
+                    if !(out.ends_with('\n') || *tight && matches!(b, Block::Paragraph(_))) {
+                        out.push('\n');
+                    }
+                    render(b, *tight, options, out);
+                }
+                *out += "</li>\n";
+            }
+            *out += &format!("</{}>\n", tag);
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        escape_char(&mut out, c);
+    }
+    out
+}
+
+fn escape_char(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        c => out.push(c),
+    }
+}
+
+fn inline(text: &str, options: &HtmlOptions) -> String {
+    let s: Vec<char> = text.chars().collect();
+    render_inline(&s, options)
+}
+
+fn render_inline(s: &[char], options: &HtmlOptions) -> String {
+    let mut out = String::new();
+    let mut closers = Closers::new();
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            '\\' if s.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                escape_char(&mut out, s[i + 1]);
+                i += 2;
+            }
+            '\\' if s.get(i + 1) == Some(&'\n') => {
+                out += "<br />\n";
+                i += 2;
+            }
+            '`' => {
+                let n = run(s, i);
+                match code_span_end(s, i, n) {
+                    Some(end) => {
+                        let code: String = s[i + n..end].iter().collect();
+                        let code = code.replace('\n', " ");
+                        // one space on both sides is there to allow a "`" at the start or the end
+                        let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                            Some(inner) if !blank(&code) => inner,
+                            _ => &code,
+                        };
+                        out += &format!("<code>{}</code>", escape(code));
+                        i = end + n;
+                    }
+                    None => {
+                        out.extend(&s[i..i + n]);
+                        i += n;
+                    }
+                }
+            }
+            '*' | '_' => i = emphasis(s, i, options, &mut closers, &mut out),
+            '[' => match link(s, i) {
+                Some(link) => {
+                    let text = render_inline(&s[i + 1..link.text_end], options);
+                    if !options.raw_html && !safe_url(&link.url) {
+                        out += &text;
+                    } else {
+                        out += &format!("<a href=\"{}\"", escape(&link.url));
+                        if let Some(title) = link.title {
+                            out += &format!(" title=\"{}\"", escape(&title));
+                        }
+                        out += &format!(">{}</a>", text);
+                    }
+                    i = link.end;
+                }
+                None => {
+                    out.push('[');
+                    i += 1;
+                }
+            },
+            '<' => {
+                let end = s[i..].iter().position(|&c| c == '>').map(|p| i + p);
+                let inside: String = end.map_or(String::new(), |e| s[i + 1..e].iter().collect());
+                match end {
+                    Some(end) if autolink(&inside) => {
+                        if options.raw_html || safe_url(&inside) {
+                            let url = escape(&inside);
+                            out += &format!("<a href=\"{}\">{}</a>", url, url);
+                        } else {
+                            out += &escape(&s[i..=end].iter().collect::<String>());
+                        }
+                        i = end + 1;
+                    }
+                    Some(end)
+                        if options.raw_html
+                            && inside
+                                .starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') =>
+                    {
+                        out.extend(&s[i..=end]);
+                        i = end + 1;
+                    }
+                    _ => {
+                        out += "&lt;";
+                        i += 1;
+                    }
+                }
+            }
+            '\n' => {
+                // two spaces at the end of the line break it
+                let text = out.trim_end_matches(' ').len();
+                let spaces = out.len() - text;
+                out.truncate(text);
+                out += if spaces >= 2 { "<br />\n" } else { "\n" };
+                i += 1;
+            }
+            c => {
+                escape_char(&mut out, c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// how many times s[i] is repeated from i
+fn run(s: &[char], i: usize) -> usize {
+    s[i..].iter().take_while(|&&c| c == s[i]).count()
+}
+
+// a code span ends with as many backticks as it starts with, no more and no less
+fn code_span_end(s: &[char], i: usize, n: usize) -> Option<usize> {
+    let mut p = i + n;
+    while p < s.len() {
+        if s[p] != '`' {
+            p += 1;
+            continue;
+        }
+        let r = run(s, p);
+        if r == n {
+            return Some(p);
+        }
+        p += r;
+    }
+    None
+}
+
+/*
+ * Emphasis, simplified from the rules of the spec: a run of "*" or "_" can open emphasis when it's
+ * followed by something other than a space, and close it when it comes after something other than
+ * a space (and "_" doesn't work inside words, as snake_case_names are common). One char gives
+ * <em>, two <strong> and three both. When the opener and the closer have different lengths, the
+ * extra chars stay as they are: "**foo*" is "*<em>foo</em>".
+ */
+fn can_open(s: &[char], i: usize, r: usize) -> bool {
+    let after = s.get(i + r).is_some_and(|c| !c.is_whitespace());
+    after && (s[i] == '*' || !(i > 0 && s[i - 1].is_alphanumeric()))
+}
+
+fn can_close(s: &[char], i: usize, r: usize) -> bool {
+    let before = i > 0 && !s[i - 1].is_whitespace();
+    before && (s[i] == '*' || !s.get(i + r).is_some_and(|c| c.is_alphanumeric()))
+}
+
+/*
+ * What find_closer found from a position, for a char and a number of them. Every run that can't
+ * close looks for its own closer in the rest of the text, and so does every run after it: without
+ * remembering the answers, "a *x a *x a *x..." takes twice as long with every "*x".
+ */
+type Closers = HashMap<(usize, char, usize), Option<usize>>;
+
+// the first run of "d" that can close a run of n of them, skipping the emphasis inside
+fn find_closer(s: &[char], from: usize, d: char, n: usize, known: &mut Closers) -> Option<usize> {
+    if let Some(&found) = known.get(&(from, d, n)) {
+        return found;
+    }
+    let mut found = None;
+    let mut p = from;
+    while p < s.len() {
+        match s[p] {
+            '\\' => p += 2,
+            '`' => {
+                let r = run(s, p);
+                p = code_span_end(s, p, r).map_or(p + r, |end| end + r);
+            }
+            c if c == d => {
+                let r = run(s, p);
+                if can_close(s, p, r) && r >= n {
+                    found = Some(p);
+                    break;
+                }
+                let inner = r.min(3);
+                match find_closer(s, p + r, d, inner, known) {
+                    Some(end) if can_open(s, p, r) => p = end + inner,
+                    _ => p += r,
+                }
+            }
+            _ => p += 1,
+        }
+    }
+    known.insert((from, d, n), found);
+    found
+}
+
+fn emphasis(
+    s: &[char],
+    i: usize,
+    options: &HtmlOptions,
+    closers: &mut Closers,
+    out: &mut String,
+) -> usize {
+    let d = s[i];
+    let r = run(s, i);
+    if can_open(s, i, r) {
+        for n in (1..=r.min(3)).rev() {
+            if let Some(end) = find_closer(s, i + r, d, n, closers) {
+                out.extend(std::iter::repeat_n(d, r - n));
+                let (open, close) = match n {
+                    1 => ("<em>", "</em>"),
+                    2 => ("<strong>", "</strong>"),
+                    _ => ("<em><strong>", "</strong></em>"),
+                };
+                let inner = render_inline(&s[i + r..end], options);
+                *out += &format!("{}{}{}", open, inner, close);
+                return end + n;
+            }
+        }
+    }
+    out.extend(&s[i..i + r]);
+    i + r
+}
+
+struct Link {
+    // where the "]" is
+    text_end: usize,
+    url: String,
+    title: Option<String>,
+    // after the ")"
+    end: usize,
+}
+
+// "[text](url "title")", with s[i] == '['
+fn link(s: &[char], i: usize) -> Option<Link> {
+    let mut depth = 0;
+    let mut p = i;
+    let text_end = loop {
+        match s.get(p)? {
+            '\\' => p += 1,
+            '`' => {
+                let r = run(s, p);
+                p = code_span_end(s, p, r).map_or(p + r, |end| end + r) - 1;
+            }
+            '[' => depth += 1,
+            ']' if depth == 1 => break p,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        p += 1;
+    };
+    if s.get(text_end + 1) != Some(&'(') {
+        return None;
+    }
+
+    let mut p = text_end + 2;
+    let skip_spaces = |p: &mut usize| {
+        while s.get(*p).is_some_and(|c| c.is_whitespace()) {
+            *p += 1;
+        }
+    };
+    skip_spaces(&mut p);
+    let mut url = String::new();
+    if s.get(p) == Some(&'<') {
+        p += 1;
+        while *s.get(p)? != '>' {
+            if s[p] == '<' || s[p] == '\n' {
+                return None;
+            }
+            url.push(s[p]);
+            p += 1;
+        }
+        p += 1;
+    } else {
+        // the parentheses in a url have to be balanced
+        let mut parens = 0;
+        while let Some(&c) = s.get(p) {
+            match c {
+                c if c.is_whitespace() => break,
+                '\\' if s.get(p + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    p += 1;
+                    url.push(s[p]);
+                }
+                '(' => {
+                    parens += 1;
+                    url.push(c);
+                }
+                ')' if parens == 0 => break,
+                ')' => {
+                    parens -= 1;
+                    url.push(c);
+                }
+                c => url.push(c),
+            }
+            p += 1;
+        }
+    }
+
+    let before_title = p;
+    skip_spaces(&mut p);
+    let mut title = None;
+    let close = match s.get(p) {
+        Some('"') => Some('"'),
+        Some('\'') => Some('\''),
+        Some('(') => Some(')'),
+        _ => None,
+    };
+    if let Some(close) = close.filter(|_| p > before_title) {
+        let mut t = String::new();
+        p += 1;
+        while *s.get(p)? != close {
+            if s[p] == '\\' && s.get(p + 1).is_some_and(|c| c.is_ascii_punctuation()) {
+                p += 1;
+            }
+            t.push(s[p]);
+            p += 1;
+        }
+        p += 1;
+        title = Some(t);
+        skip_spaces(&mut p);
+    }
+    if s.get(p) != Some(&')') {
+        return None;
+    }
+    Some(Link {
+        text_end,
+        url,
+        title,
+        end: p + 1,
+    })
+}
+
+// "scheme:anything without spaces", e.g. "https://example.com" or "mailto:ops@example.com"
+fn autolink(text: &str) -> bool {
+    let Some((scheme, rest)) = text.split_once(':') else {
+        return false;
+    };
+    (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        && !rest.contains(|c: char| c.is_whitespace() || c == '<' || c == '>')
+}
+
+/*
+ * An allowlist, as a blocklist always misses something: before reading the scheme, browsers drop
+ * the control chars and spaces around a URL, and every tab and newline inside it, so that
+ * "java\tscript:" is "javascript:". A URL with control chars is refused outright, and a relative
+ * one is one without a scheme, that is without a ':' before the first '/', '?' or '#'.
+ */
+fn safe_url(url: &str) -> bool {
+    if url.contains(|c: char| c.is_control()) {
+        return false;
+    }
+    let url = url.trim_matches(' ');
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = url[..i].to_ascii_lowercase();
+            ["http", "https", "mailto"].contains(&scheme.as_str())
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Role, User};
+
+    fn html(text: &str) -> String {
+        to_html(text, &HtmlOptions::default())
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(
+            "<h1>NOTAM</h1>\n<h3>runway 27</h3>\n<p>closed\nuntil 18:00</p>\n",
+            html("# NOTAM\n\n### runway 27 ###\nclosed\nuntil 18:00\n")
+        );
+        assert_eq!("<p>#hashtag</p>\n", html("#hashtag"));
+        assert_eq!(
+            "<pre><code class=\"language-rust\">fn main() {\n    &lt;&gt;\n}\n</code></pre>\n",
+            html("```rust\nfn main() {\n    <>\n}\n```")
+        );
+        assert_eq!(
+            "<p>text</p>\n<pre><code>indented\n\ncode\n</code></pre>\n",
+            html("text\n\n    indented\n\n    code\n\n")
+        );
+        assert_eq!(
+            "<blockquote>\n<h2>quoted</h2>\n<p>lazy\nline</p>\n</blockquote>\n",
+            html("> ## quoted\n> lazy\nline")
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            "<ul>\n<li>one</li>\n<li>two\n<ol>\n<li>three</li>\n</ol>\n</li>\n</ul>\n",
+            html("- one\n- two\n  1. three")
+        );
+        // only a list starting from 1 can interrupt a paragraph
+        assert_eq!(
+            "<p>x\n3) y</p>\n<ol start=\"3\">\n<li>y</li>\n</ol>\n",
+            html("x\n3) y\n\n3) y")
+        );
+        // a blank line between the items makes it loose
+        assert_eq!(
+            "<ol>\n<li>\n<p>one</p>\n</li>\n<li>\n<p>two</p>\n<blockquote>\n<p>q</p>\n</blockquote>\n</li>\n</ol>\n",
+            html("1. one\n\n2. two\n   > q\n")
+        );
+        // another bullet is another list
+        assert_eq!(
+            "<ul>\n<li>a</li>\n</ul>\n<ul>\n<li>b</li>\n</ul>\n<p>after</p>\n",
+            html("- a\n+ b\n\nafter")
+        );
+        // a marker followed by a space that isn't ASCII isn't one
+        assert_eq!("<p>text\n-\u{3000}</p>\n", html("text\n-\u{3000}"));
+        assert_eq!("<p>x\n1.\u{3000}</p>\n", html("x\n1.\u{3000}"));
+        assert_eq!("<p>-\u{3000}a</p>\n", html("-\u{3000}a"));
+    }
+
+    #[test]
+    fn inlines() {
+        assert_eq!(
+            "<p><em>a</em> <strong>b</strong> <em><strong>c</strong></em> *<em>d</em></p>\n",
+            html("*a* __b__ ***c*** **d*")
+        );
+        assert_eq!(
+            "<p><em>a <strong>b</strong> c</em> snake_case_name 2 * 3 * 4</p>\n",
+            html("_a **b** c_ snake_case_name 2 * 3 * 4")
+        );
+        assert_eq!(
+            "<p><code>*x* &lt;b&gt;</code> <code>a`b</code> \\*</p>\n",
+            html("`*x* <b>` `` a`b `` \\\\\\*")
+        );
+        assert_eq!(
+            "<p><a href=\"https://example.com/a_(b)\" title=\"the &quot;site&quot;\">the <em>site</em></a> \
+             <a href=\"mailto:ops@example.com\">mailto:ops@example.com</a> [not](a link</p>\n",
+            html("[the *site*](https://example.com/a_(b) \"the \\\"site\\\"\") <mailto:ops@example.com> [not](a link")
+        );
+        assert_eq!("<p>line<br />\nbreak</p>\n", html("line  \nbreak"));
+
+        // runs that never close: this one would never finish if they weren't remembered
+        let text = "a *x _y ".repeat(500);
+        assert_eq!(format!("<p>{}</p>\n", text.trim_end()), html(&text));
+    }
+
+    #[test]
+    fn raw_html() {
+        let text = "<script>alert(1)</script> [click](javascript:alert(1)) <b>bold</b>";
+        assert_eq!(
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt; click &lt;b&gt;bold&lt;/b&gt;</p>\n",
+            html(text)
+        );
+        let trusted = HtmlOptions { raw_html: true };
+        assert_eq!(
+            "<p><script>alert(1)</script> <a href=\"javascript:alert(1)\">click</a> <b>bold</b></p>\n",
+            to_html(text, &trusted)
+        );
+        // what browsers would read as "javascript:" too, and the schemes nobody thought of
+        for url in [
+            "\u{1}javascript:alert(1)",
+            "<java\tscript:alert(1)>",
+            " JavaScript:alert(1)",
+            "vbscript:x",
+            "ftp://example.com",
+        ] {
+            assert_eq!("<p>x</p>\n", html(&format!("[x]({})", url)));
+        }
+        assert_eq!(
+            "<p>&lt;ftp://example.com&gt;</p>\n",
+            html("<ftp://example.com>")
+        );
+        assert_eq!(
+            "<p><a href=\"notams/27?x=a:b#top\">x</a></p>\n",
+            html("[x](notams/27?x=a:b#top)")
+        );
+
+        // nothing before the post is published
+        let ana = User::new("ana", &[Role::Author]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let mut post = Post::new(&ana);
+        post.add_text(&ana, "# bulletin").unwrap();
+        assert_eq!("", post.render_html());
+        post.request_review(&ana).unwrap();
+        assert_eq!("", post.render_html());
+        post.approve(&bob).unwrap();
+        assert_eq!("<h1>bulletin</h1>\n", post.render_html());
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use crate::{Post, PostError};
+
+/*
+ * A PostRepository keeps posts in memory for many threads at once (the editors of a web app,
+ * say). Cloning it gives another handle on the same posts.
+ *
+ * There are two levels of locks: the map from the ids to the posts, only held while looking a
+ * post up or adding one, and a RwLock for every post. So readers of a published post never wait
+ * for someone editing a draft, as they don't touch the same lock, and many readers of the same
+ * post don't wait for each other either.
+ *
+ * The locks keep two threads from changing a post at the same time, but not an editor from
+ * changing a post that changed since they last looked at it, e.g. approving a text that was
+ * edited meanwhile. So every post has a version, bumped by every change, and an update says which
+ * version it expects: when it isn't the current one any more, the update fails with a Conflict,
+ * and the editor has to look again (optimistic concurrency, as with the ETags of HTTP).
+ *
+ * A thread that panics while holding a write lock "poisons" it, and read() and write() then return
+ * an error for everybody else. Here the map and the posts are never left half changed (a Post
+ * method changes the post completely or not at all, and the version is bumped before the panic
+ * goes on, see update), so the locks are simply taken back with into_inner, and one editor's panic
+ * doesn't take the post down for all the others.
+ */
+
+pub type Version = u64;
+
+#[derive(Debug, PartialEq)]
+pub enum RepositoryError {
+    NotFound(String),
+    Exists(String),
+    // the post changed since the version the update expected
+    Conflict {
+        id: String,
+        expected: Version,
+        actual: Version,
+    },
+    Post {
+        id: String,
+        error: PostError,
+    },
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::NotFound(id) => write!(f, "there is no post \"{}\"", id),
+            RepositoryError::Exists(id) => write!(f, "there is already a post \"{}\"", id),
+            RepositoryError::Conflict {
+                id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: changed by someone else (version {}, not {}): reload it and try again",
+                id, actual, expected
+            ),
+            RepositoryError::Post { id, error } => write!(f, "{}: {}", id, error),
+        }
+    }
+}
+
+impl Error for RepositoryError {}
+
+struct Versioned {
+    version: Version,
+    post: Post,
+}
+
+#[derive(Clone, Default)]
+pub struct PostRepository {
+    posts: Arc<RwLock<HashMap<String, Arc<RwLock<Versioned>>>>>,
+}
+
+impl PostRepository {
+    pub fn new() -> PostRepository {
+        PostRepository::default()
+    }
+
+    // a new post starts from version 1
+    pub fn insert(&self, id: &str, post: Post) -> Result<Version, RepositoryError> {
+        let mut posts = self.posts.write().unwrap_or_else(PoisonError::into_inner);
+        if posts.contains_key(id) {
+            return Err(RepositoryError::Exists(id.to_string()));
+        }
+        let entry = Versioned { version: 1, post };
+        posts.insert(id.to_string(), Arc::new(RwLock::new(entry)));
+        Ok(1)
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .posts
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .keys()
+            .cloned()
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn version(&self, id: &str) -> Result<Version, RepositoryError> {
+        self.read(id, |_, version| version)
+    }
+
+    // "f" sees the post as it is at that version, and nobody can change it meanwhile
+    pub fn read<R>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&Post, Version) -> R,
+    ) -> Result<R, RepositoryError> {
+        let entry = self.entry(id)?;
+        let entry = entry.read().unwrap_or_else(PoisonError::into_inner);
+        Ok(f(&entry.post, entry.version))
+    }
+
+    /*
+     * Changes the post if it is still at the "expected" version, and returns the new one.
+     *
+     * The Post methods leave the post as it was when they fail, so an "f" that makes one change
+     * leaves no trace when it fails. If it made some changes before failing they are kept (a Post
+     * can't be copied to go back to), and the version is bumped all the same, as a new version
+     * is what it is. The same goes when "f" panics: the version is bumped if need be, then the
+     * panic goes on to the caller.
+     */
+    pub fn update(
+        &self,
+        id: &str,
+        expected: Version,
+        f: impl FnOnce(&mut Post) -> Result<(), PostError>,
+    ) -> Result<Version, RepositoryError> {
+        let entry = self.entry(id)?;
+        let mut entry = entry.write().unwrap_or_else(PoisonError::into_inner);
+        if entry.version != expected {
+            return Err(RepositoryError::Conflict {
+                id: id.to_string(),
+                expected,
+                actual: entry.version,
+            });
+        }
+        let events = entry.post.log().len();
+        let res = panic::catch_unwind(AssertUnwindSafe(|| f(&mut entry.post)));
+        if entry.post.log().len() != events {
+            entry.version += 1;
+        }
+        let res = res.unwrap_or_else(|panic| panic::resume_unwind(panic));
+        res.map(|_| entry.version)
+            .map_err(|error| RepositoryError::Post {
+                id: id.to_string(),
+                error,
+            })
+    }
+
+    // the map is only locked while the post is looked up: the post is locked by the caller
+    fn entry(&self, id: &str) -> Result<Arc<RwLock<Versioned>>, RepositoryError> {
+        let posts = self.posts.read().unwrap_or_else(PoisonError::into_inner);
+        posts
+            .get(id)
+            .cloned()
+            .ok_or_else(|| RepositoryError::NotFound(id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{mpsc, Barrier},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{Role, Status, User};
+
+    fn users() -> (User, User) {
+        (
+            User::new("ana", &[Role::Author]),
+            User::new("bob", &[Role::Reviewer]),
+        )
+    }
+
+    #[test]
+    fn conflicts() {
+        let (ana, bob) = users();
+        let repo = PostRepository::new();
+        assert_eq!(Ok(1), repo.insert("notam", Post::new(&ana)));
+        assert_eq!(
+            Err(RepositoryError::Exists(String::from("notam"))),
+            repo.insert("notam", Post::new(&ana))
+        );
+
+        // two editors load version 1, the first one to save wins
+        let v = repo.version("notam").unwrap();
+        assert_eq!(
+            Ok(2),
+            repo.update("notam", v, |p| p.add_text(&ana, "runway"))
+        );
+        assert_eq!(
+            Err(RepositoryError::Conflict {
+                id: String::from("notam"),
+                expected: 1,
+                actual: 2
+            }),
+            repo.update("notam", v, |p| p.add_text(&ana, "taxiway"))
+        );
+
+        // a refused transition doesn't make a new version
+        assert!(matches!(
+            repo.update("notam", 2, |p| p.approve(&bob)),
+            Err(RepositoryError::Post { .. })
+        ));
+        assert_eq!(Ok(3), repo.update("notam", 2, |p| p.request_review(&ana)));
+        assert_eq!(Ok(4), repo.update("notam", 3, |p| p.approve(&bob)));
+        assert_eq!(
+            Ok((String::from("runway"), 4)),
+            repo.read("notam", |p, v| (p.content().to_string(), v))
+        );
+        assert_eq!(
+            Err(RepositoryError::NotFound(String::from("x"))),
+            repo.version("x")
+        );
+    }
+
+    // many editors appending to the same draft: every append ends up in it exactly once
+    #[test]
+    fn stress_optimistic_updates() {
+        const THREADS: usize = 16;
+        const APPENDS: usize = 200;
+        let (ana, _) = users();
+        let repo = PostRepository::new();
+        repo.insert("draft", Post::new(&ana)).unwrap();
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let (repo, ana) = (repo.clone(), &ana);
+                s.spawn(move || {
+                    for _ in 0..APPENDS {
+                        // load, change, save, and start again on a conflict
+                        loop {
+                            let v = repo.version("draft").unwrap();
+                            let text = format!("{},", t);
+                            match repo.update("draft", v, |p| p.add_text(ana, &text)) {
+                                Ok(_) => break,
+                                Err(RepositoryError::Conflict { .. }) => {}
+                                Err(e) => panic!("{}", e),
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        let (version, counts) = repo
+            .read("draft", |p, v| {
+                let content = p.revisions().pop().unwrap().content;
+                let mut counts = [0; THREADS];
+                for t in content.split_terminator(',') {
+                    counts[t.parse::<usize>().unwrap()] += 1;
+                }
+                (v, counts)
+            })
+            .unwrap();
+        assert_eq!((1 + THREADS * APPENDS) as Version, version);
+        assert_eq!([APPENDS; THREADS], counts);
+    }
+
+    // an editor that panics leaves the post usable by the others, at a new version if it changed it
+    #[test]
+    fn panicking_updates() {
+        let (ana, _) = users();
+        let repo = PostRepository::new();
+        repo.insert("notam", Post::new(&ana)).unwrap();
+        for (change, version) in [(false, 1), (true, 2)] {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                repo.update("notam", 1, |p| {
+                    if change {
+                        p.add_text(&ana, "runway")?;
+                    }
+                    panic!("the editor crashed");
+                })
+            }));
+            assert!(res.is_err());
+            assert_eq!(Ok(version), repo.version("notam"));
+        }
+        assert_eq!(
+            Ok(3),
+            repo.update("notam", 2, |p| p.add_text(&ana, " closed"))
+        );
+        assert_eq!(
+            Ok(String::from("runway closed")),
+            repo.read("notam", |p, _| p.revisions().pop().unwrap().content)
+        );
+    }
+
+    // readers of a published post aren't held up by a writer busy with a draft
+    #[test]
+    fn readers_do_not_wait_for_writers() {
+        let (ana, bob) = users();
+        let repo = PostRepository::new();
+        let mut published = Post::new(&ana);
+        published.add_text(&ana, "bulletin").unwrap();
+        published.request_review(&ana).unwrap();
+        published.approve(&bob).unwrap();
+        repo.insert("published", published).unwrap();
+        repo.insert("draft", Post::new(&ana)).unwrap();
+
+        let (locked, release) = (Barrier::new(2), Barrier::new(2));
+        let (done, reads) = mpsc::channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                repo.update("draft", 1, |p| {
+                    // the draft stays locked until every reader is done
+                    locked.wait();
+                    release.wait();
+                    p.add_text(&ana, "slow edit")
+                })
+                .unwrap();
+            });
+            locked.wait();
+            for _ in 0..8 {
+                let (repo, done) = (repo.clone(), done.clone());
+                s.spawn(move || {
+                    for _ in 0..1_000 {
+                        let content = repo
+                            .read("published", |p, _| p.content().to_string())
+                            .unwrap();
+                        assert_eq!("bulletin", content);
+                    }
+                    done.send(()).unwrap();
+                });
+            }
+            // with a single lock for everything, this would never come
+            for _ in 0..8 {
+                reads.recv_timeout(Duration::from_secs(10)).unwrap();
+            }
+            release.wait();
+        });
+        assert_eq!(Ok(2), repo.version("draft"));
+    }
+
+    // whole reviews running side by side, with readers looking at all the posts meanwhile
+    #[test]
+    fn stress_lifecycles() {
+        const POSTS: usize = 32;
+        let (ana, bob) = users();
+        let repo = PostRepository::new();
+        thread::scope(|s| {
+            for i in 0..POSTS {
+                let (repo, ana, bob) = (repo.clone(), &ana, &bob);
+                s.spawn(move || {
+                    let id = format!("post-{}", i);
+                    let mut v = repo.insert(&id, Post::new(ana)).unwrap();
+                    let text = id.clone();
+                    v = repo.update(&id, v, |p| p.add_text(ana, &text)).unwrap();
+                    v = repo.update(&id, v, |p| p.request_review(ana)).unwrap();
+                    repo.update(&id, v, |p| p.approve(bob)).unwrap();
+                });
+            }
+            for _ in 0..4 {
+                let repo = repo.clone();
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        for id in repo.ids() {
+                            // a post shows its content only once published, never half of it
+                            let content = repo.read(&id, |p, _| p.content().to_string()).unwrap();
+                            assert!(content.is_empty() || content == id);
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(POSTS, repo.ids().len());
+        for id in repo.ids() {
+            let (status, version) = repo.read(&id, |p, v| (p.status(), v)).unwrap();
+            assert_eq!((Status::Published, 4), (status, version));
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{Role, User};
+
+/*
+ * Who reviews a post, and how many of them have to agree before it is published.
+ *
+ * Every review is recorded with its reviewer while the post is pending, and after each one the
+ * policy of the post looks at all of them and decides: published (or scheduled), back to draft,
+ * or still waiting for more reviews. A reviewer can only review a post once per round.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Approved,
+    // with the reason
+    Rejected(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Review {
+    pub reviewer: User,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Policy {
+    // N approvals from N different reviewers publish the post, N rejections send it back to draft
+    Approvals(usize),
+    // the first reviewer with this role decides, the others only give their opinion
+    Role(Role),
+    // a single rejection sends the post back to draft, whatever the inner policy says
+    Veto(Box<Policy>),
+    // approved once every policy approves, rejected as soon as one of them rejects
+    All(Vec<Policy>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+// one approval is enough and one rejection too, as before there were policies
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::Veto(Box::new(Policy::Approvals(1)))
+    }
+}
+
+impl Policy {
+    pub fn decide(&self, reviews: &[Review]) -> Decision {
+        let rejected = |r: &&Review| matches!(r.verdict, Verdict::Rejected(_));
+        match self {
+            Policy::Approvals(n) => {
+                // zero approvals would mean published without a review
+                let n = (*n).max(1);
+                let no = reviews.iter().filter(rejected).count();
+                if no >= n {
+                    Decision::Rejected
+                } else if reviews.len() - no >= n {
+                    Decision::Approved
+                } else {
+                    Decision::Pending
+                }
+            }
+            Policy::Role(role) => match reviews.iter().find(|r| r.reviewer.has_role(*role)) {
+                Some(r) if rejected(&r) => Decision::Rejected,
+                Some(_) => Decision::Approved,
+                None => Decision::Pending,
+            },
+            Policy::Veto(_) if reviews.iter().any(|r| rejected(&r)) => Decision::Rejected,
+            Policy::Veto(inner) => inner.decide(reviews),
+            Policy::All(policies) => {
+                let decisions: Vec<Decision> = policies.iter().map(|p| p.decide(reviews)).collect();
+                if decisions.contains(&Decision::Rejected) {
+                    Decision::Rejected
+                } else if decisions.iter().all(|&d| d == Decision::Approved) {
+                    Decision::Approved
+                } else {
+                    Decision::Pending
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn review(name: &str, role: Role, approved: bool) -> Review {
+        Review {
+            reviewer: User::new(name, &[role]),
+            verdict: if approved {
+                Verdict::Approved
+            } else {
+                Verdict::Rejected(String::from("no"))
+            },
+        }
+    }
+
+    #[test]
+    fn policies() {
+        let two = Policy::Approvals(2);
+        let ana = review("ana", Role::Reviewer, true);
+        let bob = review("bob", Role::Reviewer, true);
+        let eve = review("eve", Role::Publisher, false);
+        assert_eq!(Decision::Pending, two.decide(std::slice::from_ref(&ana)));
+        assert_eq!(Decision::Approved, two.decide(&[ana.clone(), bob.clone()]));
+        // one rejection isn't enough to send it back
+        assert_eq!(Decision::Pending, two.decide(&[ana.clone(), eve.clone()]));
+        let veto = Policy::Veto(Box::new(two));
+        assert_eq!(Decision::Rejected, veto.decide(&[ana.clone(), eve.clone()]));
+
+        let publisher = Policy::Role(Role::Publisher);
+        assert_eq!(
+            Decision::Pending,
+            publisher.decide(&[ana.clone(), bob.clone()])
+        );
+        assert_eq!(Decision::Rejected, publisher.decide(&[ana.clone(), eve]));
+
+        let both = Policy::All(vec![Policy::Approvals(2), publisher]);
+        let sam = review("sam", Role::Publisher, true);
+        assert_eq!(Decision::Pending, both.decide(&[ana.clone(), bob.clone()]));
+        assert_eq!(Decision::Approved, both.decide(&[ana, bob, sam]));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{diff, Action, Post, PostError, Timestamp};
+
+/*
+ * Every change of content makes a new revision, numbered from 0, the empty post just created.
+ *
+ * The revisions aren't stored anywhere: the log already has every add_text, edit and revert in
+ * order, so going through it again gives the content of each of them. Reverting doesn't rewrite
+ * the history either, it adds a revision with an old content, and it can be reverted in turn.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    pub number: usize,
+    pub at: Timestamp,
+    // who made it
+    pub author: String,
+    pub content: String,
+}
+
+impl Post {
+    pub fn revisions(&self) -> Vec<Revision> {
+        let created = &self.log[0];
+        let mut revisions = vec![Revision {
+            number: 0,
+            at: created.at,
+            author: created.actor.name.clone(),
+            content: String::new(),
+        }];
+        for e in &self.log {
+            let last = &revisions[revisions.len() - 1].content;
+            let content = match &e.action {
+                Action::AddText(text) => format!("{}{}", last, text),
+                Action::Edit(text) => text.clone(),
+                // always there in a log that was checked
+                Action::Revert(n) => match revisions.get(*n) {
+                    Some(r) => r.content.clone(),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            revisions.push(Revision {
+                number: revisions.len(),
+                at: e.at,
+                author: e.actor.name.clone(),
+                content,
+            });
+        }
+        revisions
+    }
+
+    pub fn latest_revision(&self) -> usize {
+        self.log
+            .iter()
+            .filter(|e| e.action.changes_content())
+            .count()
+    }
+
+    // what changed from one revision to the other, in the unified format
+    pub fn diff(&self, from: usize, to: usize) -> Result<String, PostError> {
+        let revisions = self.revisions();
+        let latest = revisions.len() - 1;
+        let get = |revision: usize| {
+            revisions
+                .get(revision)
+                .ok_or(PostError::UnknownRevision { revision, latest })
+        };
+        let (a, b) = (get(from)?, get(to)?);
+        Ok(diff::unified(
+            &format!("revision {}", from),
+            &format!("revision {}", to),
+            &a.content,
+            &b.content,
+            3,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{FixedClock, Policy, Role, Status, User};
+
+    use super::*;
+
+    #[test]
+    fn revisions() {
+        let ana = User::new("ana", &[Role::Author]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let mut post = Post::with_clock(&ana, Policy::default(), Arc::new(FixedClock(10)));
+        post.add_text(&ana, "NOTAM\nrunway 27 closed\n").unwrap();
+        post.edit(&ana, "NOTAM\nrunway 09 closed\nuntil 18:00\n")
+            .unwrap();
+        assert_eq!(2, post.latest_revision());
+        assert_eq!(
+            "--- revision 1\n+++ revision 2\n\
+             @@ -1,2 +1,3 @@\n NOTAM\n-runway 27 closed\n+runway 09 closed\n+until 18:00\n",
+            post.diff(1, 2).unwrap()
+        );
+
+        post.revert(&ana, 1).unwrap();
+        let revisions = post.revisions();
+        assert_eq!(4, revisions.len());
+        assert_eq!(revisions[1].content, revisions[3].content);
+        assert_eq!("", post.diff(1, 3).unwrap());
+        assert_eq!(
+            Err(PostError::UnknownRevision {
+                revision: 9,
+                latest: 3
+            }),
+            post.revert(&ana, 9)
+        );
+
+        // the content of a post under review is frozen
+        post.request_review(&ana).unwrap();
+        assert_eq!(
+            Err(PostError::InvalidTransition {
+                action: "revert",
+                status: Status::PendingReview
+            }),
+            post.revert(&ana, 2)
+        );
+        post.approve(&bob).unwrap();
+        assert_eq!("NOTAM\nrunway 27 closed\n", post.content());
+
+        // and the revisions come back with the log
+        let copy = Post::replay(post.log()).unwrap();
+        assert_eq!(revisions, copy.revisions());
+    }
+}
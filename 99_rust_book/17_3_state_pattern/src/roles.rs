@@ -0,0 +1,134 @@
+use std::fmt;
+
+use crate::{Action, PostError, Verdict};
+
+/*
+ * Who can do what to a post:
+ * - authors write their own posts and send them to review
+ * - reviewers approve or reject the posts of the others
+ * - publishers do the same, and can also schedule them
+ * - admins can do everything, but approving their own posts: nobody can
+ *
+ * This is only about the people: whether the post is in a state where that can be done at all is
+ * up to the states themselves.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Author,
+    Reviewer,
+    Publisher,
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Author => "author",
+            Role::Reviewer => "reviewer",
+            Role::Publisher => "publisher",
+            Role::Admin => "admin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub roles: Vec<Role>,
+}
+
+impl User {
+    pub fn new(name: &str, roles: &[Role]) -> User {
+        User {
+            name: name.to_string(),
+            roles: roles.to_vec(),
+        }
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.contains(&role)
+    }
+
+    fn has_any(&self, roles: &[Role]) -> bool {
+        roles.iter().any(|&r| self.has_role(r))
+    }
+}
+
+// whether "actor" can do "action" to a post written by "author"
+pub fn permit(actor: &User, author: &User, action: &Action) -> Result<(), PostError> {
+    let deny = |reason: &'static str| {
+        Err(PostError::PermissionDenied {
+            actor: actor.name.clone(),
+            action: action.name(),
+            reason,
+        })
+    };
+    let own = actor.name == author.name;
+    match action {
+        // anybody can start a post, it is theirs
+        Action::Create(_) | Action::PublishDue => Ok(()),
+        Action::AddText(_) | Action::Edit(_) | Action::Revert(_) | Action::RequestReview => {
+            if actor.has_role(Role::Admin) || (own && actor.has_role(Role::Author)) {
+                Ok(())
+            } else {
+                deny("only its author can change a post")
+            }
+        }
+        Action::Review(verdict, at) => {
+            let roles: &[Role] = match at {
+                Some(_) => &[Role::Publisher, Role::Admin],
+                None => &[Role::Reviewer, Role::Publisher, Role::Admin],
+            };
+            if !actor.has_any(roles) {
+                return deny(if at.is_some() {
+                    "only publishers can schedule a post"
+                } else {
+                    "only reviewers can review a post"
+                });
+            }
+            match verdict {
+                Verdict::Approved if own => deny("nobody can approve their own post"),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn permissions() {
+        let ana = User::new("ana", &[Role::Author, Role::Reviewer]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let pat = User::new("pat", &[Role::Publisher]);
+        let root = User::new("root", &[Role::Admin]);
+        let text = Action::AddText(String::from("x"));
+        let approve = Action::Review(Verdict::Approved, None);
+        let schedule = Action::Review(Verdict::Approved, Some(100));
+        let reject = Action::Review(Verdict::Rejected(String::from("no")), None);
+
+        assert!(permit(&ana, &ana, &text).is_ok());
+        assert!(permit(&bob, &ana, &text).is_err());
+        assert!(permit(&root, &ana, &text).is_ok());
+
+        assert!(permit(&bob, &ana, &approve).is_ok());
+        assert_eq!(
+            Err(PostError::PermissionDenied {
+                actor: String::from("ana"),
+                action: "approve",
+                reason: "nobody can approve their own post"
+            }),
+            permit(&ana, &ana, &approve)
+        );
+        // but withdrawing it is fine
+        assert!(permit(&ana, &ana, &reject).is_ok());
+        assert!(permit(&root, &root, &schedule).is_err());
+
+        assert!(permit(&bob, &ana, &schedule).is_err());
+        assert!(permit(&pat, &ana, &schedule).is_ok());
+    }
+}
@@ -0,0 +1,711 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    Action, Draft, EncodedDraft, EncodedPendingReview, EncodedPost, EncodedScheduled, Event,
+    PendingReview, Policy, Post, Published, Review, Role, Scheduled, State, Status, SystemClock,
+    Timestamp, User, Verdict,
+};
+
+/*
+ * A PostStore keeps every post in its own file, "<id>.post", in a directory. The file is text, so
+ * it can be read (and fixed, carefully) by hand:
+ *
+ *   post-format 2
+ *   state: pending-review
+ *   author: ana author,reviewer
+ *   policy: veto(approvals(2))
+ *   review: bob reviewer approved
+ *   event: 1700000000 ana author,reviewer draft draft create veto(approvals(2))
+ *   event: 1700000005 ana author,reviewer draft draft add-text NOTAM: runway 27 closed
+ *   ...
+ *
+ *   NOTAM: runway 27 closed
+ *
+ * The header describes the state the post is in, so loading it doesn't need to replay the log
+ * (which is still saved, for the audit trail), and the content follows the first empty line as
+ * it is. In the header, users are a name and their roles ("-" for none), and the texts that can
+ * hold anything (the content added, the rejection reasons) come last on their line, with "\\",
+ * "\n" and "\r" escaped; so are the spaces in the names.
+ *
+ * The first line is the version of the format: I bump it whenever a change would make the files
+ * unreadable for the code that is out there, so that code can say so instead of misreading them.
+ */
+
+// the version of the format this code writes, and the newest one it reads
+// (2 added the "edit" and "revert" events)
+pub const FORMAT: u32 = 2;
+const EXTENSION: &str = "post";
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    NotFound(String),
+    // ids are file names: letters, digits, "-" and "_" only
+    InvalidId(String),
+    // written by a newer version, which knows things this one doesn't
+    NewerFormat {
+        path: PathBuf,
+        version: u32,
+    },
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "{}", e),
+            StoreError::NotFound(id) => write!(f, "there is no post \"{}\"", id),
+            StoreError::InvalidId(id) => write!(
+                f,
+                "invalid post id \"{}\": only letters, digits, \"-\" and \"_\" are allowed",
+                id
+            ),
+            StoreError::NewerFormat { path, version } => write!(
+                f,
+                "{}: written in format {}, but this version only reads up to format {}: upgrade to open it",
+                path.display(),
+                version,
+                FORMAT
+            ),
+            StoreError::Corrupt { path, line, reason } => {
+                write!(f, "{}:{}: {}", path.display(), line, reason)
+            }
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+// a post loaded into the typestate API: there is no policy there, so a pending post loses its
+// reviews and will be published by the next approval
+pub enum Stored {
+    Draft(EncodedDraft),
+    PendingReview(EncodedPendingReview),
+    Scheduled(EncodedScheduled),
+    Published(EncodedPost),
+}
+
+pub struct PostStore {
+    dir: PathBuf,
+}
+
+impl PostStore {
+    // the directory is created if it doesn't exist
+    pub fn open(dir: impl AsRef<Path>) -> Result<PostStore, StoreError> {
+        fs::create_dir_all(&dir)?;
+        Ok(PostStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // the ids of the posts in the store, sorted
+    pub fn ids(&self) -> Result<Vec<String>, StoreError> {
+        let mut ids = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                if valid_id(id) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    pub fn exists(&self, id: &str) -> Result<bool, StoreError> {
+        Ok(self.path(id)?.exists())
+    }
+
+    // replaces the post with the same id, if there is one
+    pub fn save(&self, id: &str, post: &Post) -> Result<(), StoreError> {
+        let path = self.path(id)?;
+        write_atomic(&path, encode(post).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(&self, id: &str) -> Result<Post, StoreError> {
+        let path = self.path(id)?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(StoreError::NotFound(id.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        decode(&text).map_err(|e| match e {
+            DecodeError::Newer(version) => StoreError::NewerFormat { path, version },
+            DecodeError::Invalid(line, reason) => StoreError::Corrupt { path, line, reason },
+        })
+    }
+
+    pub fn load_encoded(&self, id: &str) -> Result<Stored, StoreError> {
+        let post = self.load(id)?;
+        let content = post.content.clone();
+        Ok(match post.status() {
+            Status::Draft => Stored::Draft(EncodedDraft {
+                content,
+                rejection: post.rejection().map(String::from),
+            }),
+            Status::PendingReview => Stored::PendingReview(EncodedPendingReview { content }),
+            Status::Scheduled => Stored::Scheduled(EncodedScheduled {
+                content,
+                // always there for a scheduled post
+                at: post.scheduled_at().unwrap_or_default(),
+            }),
+            Status::Published => Stored::Published(EncodedPost { content }),
+        })
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, StoreError> {
+        if !valid_id(id) {
+            return Err(StoreError::InvalidId(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.{}", id, EXTENSION)))
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// the new content goes to a temporary file, which is then renamed over the old one: a rename is
+// atomic, so a crash while writing never leaves half a post behind
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    // every save has its own temporary file, or two saves at once (from two threads, or two
+    // processes) would write into the same one, and one of them could rename the other's half
+    static SAVES: AtomicUsize = AtomicUsize::new(0);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let n = SAVES.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), n));
+    let res = (|| {
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(content)?;
+        // the data must be on disk before the rename makes it visible
+        f.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res?;
+    sync_dir(path)
+}
+
+// the rename itself is only on disk once the directory is
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => fs::File::open(dir)?.sync_all(),
+        _ => fs::File::open(".")?.sync_all(),
+    }
+}
+
+// a directory can't be opened like a file on windows, and renames there are durable anyway
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn encode(post: &Post) -> String {
+    let mut out = format!("post-format {}\n", FORMAT);
+    let status = post.status();
+    out += &format!("state: {}\n", status_name(status));
+    out += &format!("author: {}\n", encode_user(&post.author));
+    out += &format!("policy: {}\n", encode_policy(&post.policy));
+    if let Some(at) = post.scheduled_at() {
+        out += &format!("scheduled-at: {}\n", at);
+    }
+    if let Some(reason) = post.rejection() {
+        out += &format!("rejection: {}\n", escape(reason, false));
+    }
+    for r in post.reviews() {
+        let verdict = encode_verdict(&r.verdict);
+        out += &format!("review: {} {}\n", encode_user(&r.reviewer), verdict);
+    }
+    for e in &post.log {
+        out += &format!(
+            "event: {} {} {} {} {}\n",
+            e.at,
+            encode_user(&e.actor),
+            status_name(e.from),
+            status_name(e.to),
+            encode_action(&e.action)
+        );
+    }
+    out.push('\n');
+    out.push_str(&post.content);
+    out
+}
+
+enum DecodeError {
+    Newer(u32),
+    // the line (starting from 1) and what's wrong with it
+    Invalid(usize, String),
+}
+
+fn decode(text: &str) -> Result<Post, DecodeError> {
+    let mut lines = text.split('\n').enumerate().map(|(i, l)| (i + 1, l));
+    let version = lines
+        .next()
+        .and_then(|(_, l)| l.strip_prefix("post-format "))
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| DecodeError::Invalid(1, String::from("not a post file")))?;
+    if version > FORMAT {
+        return Err(DecodeError::Newer(version));
+    }
+
+    let (mut status, mut author, mut policy) = (None, None, None);
+    let (mut at, mut rejection, mut reviews, mut log) = (None, None, vec![], vec![]);
+    let mut end = 1;
+    for (i, line) in lines.by_ref() {
+        end = i;
+        if line.is_empty() {
+            break;
+        }
+        let invalid = |reason: String| DecodeError::Invalid(i, reason);
+        let (key, value) = line
+            .split_once(": ")
+            .ok_or_else(|| invalid(String::from("expected \"key: value\"")))?;
+        let mut fields = Fields(value);
+        match key {
+            "state" => status = Some(parse_status(value).map_err(invalid)?),
+            "author" => author = Some(fields.user().map_err(invalid)?),
+            "policy" => policy = Some(parse_policy(value).map_err(invalid)?),
+            "scheduled-at" => at = Some(parse_number(value).map_err(invalid)?),
+            "rejection" => rejection = Some(unescape(value).map_err(invalid)?),
+            "review" => {
+                let reviewer = fields.user().map_err(invalid)?;
+                let verdict = fields.verdict().map_err(invalid)?;
+                reviews.push(Review { reviewer, verdict });
+            }
+            "event" => log.push(fields.event().map_err(invalid)?),
+            _ => return Err(invalid(format!("unknown key \"{}\"", key))),
+        }
+    }
+    // whatever follows the empty line is the content
+    let content: Vec<&str> = lines.map(|(_, l)| l).collect();
+    let content = content.join("\n");
+
+    let missing = |key: &str| DecodeError::Invalid(end, format!("\"{}\" is missing", key));
+    let status = status.ok_or_else(|| missing("state"))?;
+    let author = author.ok_or_else(|| missing("author"))?;
+    let policy = policy.ok_or_else(|| missing("policy"))?;
+    let invalid = |reason: &str| DecodeError::Invalid(end, reason.to_string());
+    if rejection.is_some() && status != Status::Draft {
+        return Err(invalid("only a draft has a rejection"));
+    }
+    if !reviews.is_empty() && status != Status::PendingReview {
+        return Err(invalid("only a post pending review has reviews"));
+    }
+    match (log.first(), log.last()) {
+        (
+            Some(Event {
+                action: Action::Create(_),
+                ..
+            }),
+            Some(last),
+        ) if last.to == status => {}
+        _ => return Err(invalid("the log doesn't lead to the state of the post")),
+    }
+
+    let state: Box<dyn State> = match status {
+        Status::Draft => Box::new(Draft { rejection }),
+        Status::PendingReview => Box::new(PendingReview { reviews, at }),
+        Status::Scheduled => Box::new(Scheduled {
+            at: at.ok_or_else(|| missing("scheduled-at"))?,
+        }),
+        Status::Published => Box::new(Published {}),
+    };
+    Ok(Post {
+        state: Some(state),
+        content,
+        policy,
+        author,
+        clock: Arc::new(SystemClock),
+        log,
+    })
+}
+
+// the values of a header line, separated by single spaces
+struct Fields<'a>(&'a str);
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Result<&'a str, String> {
+        if self.0.is_empty() {
+            return Err(String::from("the line ends too early"));
+        }
+        let (field, rest) = self.0.split_once(' ').unwrap_or((self.0, ""));
+        self.0 = rest;
+        Ok(field)
+    }
+
+    // everything left, as a text that can hold spaces
+    fn rest(&mut self) -> Result<String, String> {
+        unescape(std::mem::take(&mut self.0))
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.0 {
+            "" => Ok(()),
+            extra => Err(format!("unexpected \"{}\"", extra)),
+        }
+    }
+
+    fn user(&mut self) -> Result<User, String> {
+        let name = unescape(self.next()?)?;
+        let roles = match self.next()? {
+            "-" => vec![],
+            roles => roles.split(',').map(parse_role).collect::<Result<_, _>>()?,
+        };
+        Ok(User { name, roles })
+    }
+
+    fn verdict(&mut self) -> Result<Verdict, String> {
+        match self.next()? {
+            "approved" => self.end().map(|_| Verdict::Approved),
+            "rejected" => Ok(Verdict::Rejected(self.rest()?)),
+            other => Err(format!("unknown verdict \"{}\"", other)),
+        }
+    }
+
+    fn event(&mut self) -> Result<Event, String> {
+        let at = parse_number(self.next()?)?;
+        let actor = self.user()?;
+        let from = parse_status(self.next()?)?;
+        let to = parse_status(self.next()?)?;
+        let action = match self.next()? {
+            "create" => Action::Create(parse_policy(self.next()?)?),
+            "add-text" => Action::AddText(self.rest()?),
+            "edit" => Action::Edit(self.rest()?),
+            "revert" => Action::Revert(parse_number(self.next()?)? as usize),
+            "request-review" => Action::RequestReview,
+            "review" => {
+                let when = match self.next()? {
+                    "-" => None,
+                    n => Some(parse_number(n)?),
+                };
+                Action::Review(self.verdict()?, when)
+            }
+            "publish-due" => Action::PublishDue,
+            other => return Err(format!("unknown action \"{}\"", other)),
+        };
+        self.end()?;
+        Ok(Event {
+            at,
+            actor,
+            from,
+            to,
+            action,
+        })
+    }
+}
+
+fn encode_user(user: &User) -> String {
+    let roles: Vec<String> = user.roles.iter().map(|r| r.to_string()).collect();
+    let roles = if roles.is_empty() {
+        String::from("-")
+    } else {
+        roles.join(",")
+    };
+    format!("{} {}", escape(&user.name, true), roles)
+}
+
+fn encode_verdict(verdict: &Verdict) -> String {
+    match verdict {
+        Verdict::Approved => String::from("approved"),
+        Verdict::Rejected(reason) => format!("rejected {}", escape(reason, false)),
+    }
+}
+
+fn encode_action(action: &Action) -> String {
+    match action {
+        Action::Create(policy) => format!("create {}", encode_policy(policy)),
+        Action::AddText(text) => format!("add-text {}", escape(text, false)),
+        Action::Edit(text) => format!("edit {}", escape(text, false)),
+        Action::Revert(revision) => format!("revert {}", revision),
+        Action::RequestReview => String::from("request-review"),
+        Action::Review(verdict, at) => {
+            let at = at.map_or(String::from("-"), |at| at.to_string());
+            format!("review {} {}", at, encode_verdict(verdict))
+        }
+        Action::PublishDue => String::from("publish-due"),
+    }
+}
+
+// e.g. "veto(all(approvals(2),role(publisher)))"
+pub(crate) fn encode_policy(policy: &Policy) -> String {
+    match policy {
+        Policy::Approvals(n) => format!("approvals({})", n),
+        Policy::Role(role) => format!("role({})", role),
+        Policy::Veto(inner) => format!("veto({})", encode_policy(inner)),
+        Policy::All(policies) => {
+            let inner: Vec<String> = policies.iter().map(encode_policy).collect();
+            format!("all({})", inner.join(","))
+        }
+    }
+}
+
+pub(crate) fn parse_policy(text: &str) -> Result<Policy, String> {
+    let invalid = || format!("invalid policy \"{}\"", text);
+    let (name, args) = text
+        .strip_suffix(')')
+        .and_then(|t| t.split_once('('))
+        .ok_or_else(invalid)?;
+    match name {
+        "approvals" => Ok(Policy::Approvals(args.parse().map_err(|_| invalid())?)),
+        "role" => Ok(Policy::Role(parse_role(args)?)),
+        "veto" => Ok(Policy::Veto(Box::new(parse_policy(args)?))),
+        "all" => {
+            // the commas between the policies, not the ones inside them
+            let (mut policies, mut depth, mut start) = (vec![], 0, 0);
+            for (i, c) in args.char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    ',' if depth == 0 => {
+                        policies.push(parse_policy(&args[start..i])?);
+                        start = i + 1;
+                    }
+                    _ => {}
+                }
+            }
+            if !args.is_empty() {
+                policies.push(parse_policy(&args[start..])?);
+            }
+            Ok(Policy::All(policies))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+pub(crate) fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Draft => "draft",
+        Status::PendingReview => "pending-review",
+        Status::Scheduled => "scheduled",
+        Status::Published => "published",
+    }
+}
+
+pub(crate) fn parse_status(name: &str) -> Result<Status, String> {
+    [
+        Status::Draft,
+        Status::PendingReview,
+        Status::Scheduled,
+        Status::Published,
+    ]
+    .into_iter()
+    .find(|&s| status_name(s) == name)
+    .ok_or_else(|| format!("unknown state \"{}\"", name))
+}
+
+pub(crate) fn parse_role(name: &str) -> Result<Role, String> {
+    [Role::Author, Role::Reviewer, Role::Publisher, Role::Admin]
+        .into_iter()
+        .find(|r| r.to_string() == name)
+        .ok_or_else(|| format!("unknown role \"{}\"", name))
+}
+
+fn parse_number(text: &str) -> Result<Timestamp, String> {
+    text.parse()
+        .map_err(|_| format!("invalid number \"{}\"", text))
+}
+
+// "spaces" for the values that are followed by others on the same line
+fn escape(text: &str, spaces: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            ' ' if spaces => out.push_str("\\s"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('s') => out.push(' '),
+            _ => return Err(format!("invalid escape in \"{}\"", text)),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FixedClock;
+
+    fn store(name: &str) -> PostStore {
+        let dir = std::env::temp_dir().join(format!(
+            "state-pattern-store-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        PostStore::open(dir).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let store = store("round-trip");
+        let ana = User::new("ana maria", &[Role::Author]);
+        let bob = User::new("bob", &[Role::Reviewer]);
+        let pat = User::new("pat", &[Role::Publisher]);
+        let policy = Policy::Veto(Box::new(Policy::All(vec![
+            Policy::Approvals(2),
+            Policy::Role(Role::Publisher),
+        ])));
+        let mut post = Post::with_clock(&ana, policy, Arc::new(FixedClock(10)));
+        post.add_text(&ana, "NOTAM:\nrunway 27 \\ closed\n")
+            .unwrap();
+        post.request_review(&ana).unwrap();
+        post.reject(&bob, "which\nairport?").unwrap();
+        store.save("notam-27", &post).unwrap();
+        let copy = store.load("notam-27").unwrap();
+        assert_eq!(Status::Draft, copy.status());
+        assert_eq!(Some("which\nairport?"), copy.rejection());
+        assert_eq!(post.log(), copy.log());
+        assert_eq!(post.policy, copy.policy);
+
+        post.request_review(&ana).unwrap();
+        post.schedule(&pat, 100).unwrap();
+        store.save("notam-27", &post).unwrap();
+        let mut copy = store.load("notam-27").unwrap();
+        assert_eq!(Status::PendingReview, copy.status());
+        assert_eq!(Some(100), copy.scheduled_at());
+        assert_eq!("pat", copy.reviews()[0].reviewer.name);
+        // and the loaded post goes on from there
+        copy.approve(&bob).unwrap();
+        assert_eq!(Status::Scheduled, copy.status());
+        copy.publish_due(&FixedClock(100));
+        store.save("notam-27", &copy).unwrap();
+        let copy = store.load("notam-27").unwrap();
+        assert_eq!("NOTAM:\nrunway 27 \\ closed\n", copy.content());
+        assert_eq!(Some(&ana), copy.log().first().map(|e| &e.actor));
+
+        assert!(matches!(
+            store.load_encoded("notam-27"),
+            Ok(Stored::Published(p)) if p.content() == copy.content()
+        ));
+        assert_eq!(vec!["notam-27"], store.ids().unwrap());
+        // the temporary file is gone
+        assert_eq!(1, fs::read_dir(store.dir()).unwrap().count());
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    // the last save wins, but none of them fails or leaves a temporary file behind
+    #[test]
+    fn concurrent_saves() {
+        let store = store("concurrent-saves");
+        let ana = User::new("ana", &[Role::Author]);
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let (store, ana) = (&store, &ana);
+                s.spawn(move || {
+                    for i in 0..20 {
+                        let mut post = Post::new(ana);
+                        post.add_text(ana, &format!("{} {}", t, i)).unwrap();
+                        store.save("post", &post).unwrap();
+                    }
+                });
+            }
+        });
+        let text = store
+            .load("post")
+            .unwrap()
+            .revisions()
+            .pop()
+            .unwrap()
+            .content;
+        assert!(text.ends_with(" 19"));
+        assert_eq!(1, fs::read_dir(store.dir()).unwrap().count());
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let store = store("errors");
+        let ana = User::new("ana", &[Role::Author]);
+        assert!(matches!(
+            store.save("../x", &Post::new(&ana)),
+            Err(StoreError::InvalidId(_))
+        ));
+        assert!(matches!(
+            store.load("nothing"),
+            Err(StoreError::NotFound(_))
+        ));
+
+        store.save("post", &Post::new(&ana)).unwrap();
+        let path = store.dir().join("post.post");
+        let text = fs::read_to_string(&path).unwrap();
+        let newer = format!("post-format {}", FORMAT + 1);
+        fs::write(&path, text.replacen("post-format 2", &newer, 1)).unwrap();
+        let Err(err) = store.load("post") else {
+            panic!("a newer format was loaded")
+        };
+        assert!(matches!(err, StoreError::NewerFormat { version: 3, .. }));
+        // the older ones are still read
+        fs::write(&path, text.replacen("post-format 2", "post-format 1", 1)).unwrap();
+        assert!(store.load("post").is_ok());
+        assert!(err.to_string().contains("upgrade"));
+
+        fs::write(&path, text.replacen("state: draft", "state: lost", 1)).unwrap();
+        assert!(matches!(
+            store.load("post"),
+            Err(StoreError::Corrupt { line: 2, reason, .. }) if reason == "unknown state \"lost\""
+        ));
+        // the state and the log don't agree
+        fs::write(&path, text.replacen("state: draft", "state: published", 1)).unwrap();
+        assert!(matches!(
+            store.load("post"),
+            Err(StoreError::Corrupt { .. })
+        ));
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+}